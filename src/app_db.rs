@@ -1,11 +1,24 @@
-use crate::{epi2me_db, epi2me_desktop_analysis::Epi2meDesktopAnalysis};
+use crate::{
+    app_db_backup, app_db_lock,
+    app_db_schema::{self, BS_TABLE_SCHEMA},
+    epi2me_db::Epi2meSetup,
+    epi2me_desktop_analysis::Epi2meDesktopAnalysis,
+    epi2me_tar,
+    epi4you_errors::Epi4youError,
+    progress::Progress,
+};
 use chrono::{DateTime, Local};
-use rusqlite::Connection;
-use std::{fs, path::PathBuf};
+use rusqlite::{params_from_iter, Connection, TransactionBehavior};
+use serde::Serialize;
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
 use ulid::Ulid;
 
 #[allow(non_snake_case)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Epi2MeAnalysis {
     pub id: String,
     pub path: String,
@@ -19,63 +32,100 @@ pub struct Epi2MeAnalysis {
     pub updatedAt: String,
 }
 
-fn insert_into_db(path: &PathBuf, epi2meitem: &Epi2MeAnalysis) {
-    let conn = match Connection::open(path) {
-        Ok(conn) => conn,
-        Err(_) => {
-            println!("fubar creating db connection");
-            return;
-        }
-    };
-
-    let insert = "INSERT into bs (id, path, name, status, workflowRepo, workflowUser, workflowCommit, workflowVersion, createdAt, updatedAt) values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)";
-    let result = conn.execute(
-        insert,
-        &[
-            &epi2meitem.id,
-            &epi2meitem.path,
-            &epi2meitem.name,
-            &epi2meitem.status,
-            &epi2meitem.workflowRepo,
-            &epi2meitem.workflowUser,
-            &epi2meitem.workflowCommit,
-            &epi2meitem.workflowVersion,
-            &epi2meitem.createdAt,
-            &epi2meitem.updatedAt,
-        ],
-    );
+impl Epi2MeAnalysis {
+    /// `bs` column names that this model knows how to populate.
+    pub const FIELDS: [&'static str; 10] = [
+        "id",
+        "path",
+        "name",
+        "status",
+        "workflowRepo",
+        "workflowUser",
+        "workflowCommit",
+        "workflowVersion",
+        "createdAt",
+        "updatedAt",
+    ];
 
-    if result.is_err() {
-        println!("failure --- \n{:?}", result.err());
+    /// Returns the value destined for the named `bs` column, if known.
+    pub fn get_field(&self, column: &str) -> Option<&String> {
+        match column {
+            "id" => Some(&self.id),
+            "path" => Some(&self.path),
+            "name" => Some(&self.name),
+            "status" => Some(&self.status),
+            "workflowRepo" => Some(&self.workflowRepo),
+            "workflowUser" => Some(&self.workflowUser),
+            "workflowCommit" => Some(&self.workflowCommit),
+            "workflowVersion" => Some(&self.workflowVersion),
+            "createdAt" => Some(&self.createdAt),
+            "updatedAt" => Some(&self.updatedAt),
+            _ => None,
+        }
     }
 }
 
-fn resync_progress_json(source: &str, ulid: &str, newlid: &str) {
+/// Creates (or completes) an `app.db` with the schema Desktop expects.
+pub fn create_app_db(path: &PathBuf) -> Result<(), Epi4youError> {
+    let conn = Connection::open(path)
+        .map_err(|err| Epi4youError::FailedToOpenDatabase(path.clone(), err.into()))?;
+    conn.execute(BS_TABLE_SCHEMA, [])
+        .map_err(|err| Epi4youError::FailedToQueryDatabase(err.into()))?;
+    log::info!("app.db schema ready at [{}]", path.display());
+    Ok(())
+}
+
+fn insert_into_db(setup: &Epi2meSetup, epi2meitem: &Epi2MeAnalysis) -> Result<(), Epi4youError> {
+    let path = &setup.epi2db_path;
+    let mut conn = app_db_lock::open_for_write(path, setup.wait_for_desktop)?;
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(|err| app_db_lock::map_write_error(path, err))?;
+    // snapshot under the write lock so no other commit slips in between
+    app_db_backup::backup_app_db(setup)?;
+
+    let columns = app_db_schema::read_bs_columns(&tx)?;
+    let insert = app_db_schema::plan_bs_insert(&columns, epi2meitem)?;
+
+    tx.execute(&insert.get_sql(), params_from_iter(insert.values))
+        .map_err(|err| app_db_lock::map_write_error(path, err))?;
+    tx.commit()
+        .map_err(|err| app_db_lock::map_write_error(path, err))?;
+    Ok(())
+}
+
+fn resync_progress_json(source: &str, ulid: &str, newlid: &str) -> Result<(), Epi4youError> {
     let file2mod = ["progress.json", "params.json", "launch.json"];
-    let paths = fs::read_dir(source).unwrap();
+    let paths = fs::read_dir(source)
+        .map_err(|err| Epi4youError::FailedToReadPath(PathBuf::from(source), err.into()))?;
     for path in paths {
-        let xpath = path.unwrap().path().clone();
-        let fname = xpath.file_name().unwrap().to_string_lossy().to_string();
+        let xpath = path
+            .map_err(|err| Epi4youError::FailedToReadPath(PathBuf::from(source), err.into()))?
+            .path();
+        let fname = xpath
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
 
         if file2mod.contains(&fname.as_str()) {
-            let contents = fs::read_to_string(&xpath).unwrap();
+            let contents = fs::read_to_string(&xpath)
+                .map_err(|err| Epi4youError::FailedToReadPath(xpath.clone(), err.into()))?;
             let updated = contents.replace(ulid, newlid);
 
-            let status = fs::write(&xpath, updated);
-            if status.is_err() {
-                println!("error with writing file - {:?}", status.err());
-            }
+            fs::write(&xpath, updated)
+                .map_err(|err| Epi4youError::FailedToWritePath(xpath.clone(), err.into()))?;
         }
     }
+    Ok(())
 }
 
-fn epi2me_item_rebrand(epi2meitem: &Epi2MeAnalysis) -> Epi2MeAnalysis {
+fn epi2me_item_rebrand(epi2meitem: &Epi2MeAnalysis, setup: &Epi2meSetup) -> Epi2MeAnalysis {
     let mut epi2meitem_x = epi2meitem.clone();
     epi2meitem_x.id = Ulid::new().to_string();
 
-    let mut dst_dir = epi2me_db::find_db().unwrap().instances_path;
+    let mut dst_dir = setup.instances_path.clone();
     dst_dir.push(vec![epi2meitem_x.workflowRepo.clone(), epi2meitem_x.id.clone()].join("_"));
-    epi2meitem_x.path = dst_dir.into_os_string().into_string().unwrap();
+    epi2meitem_x.path = dst_dir.to_string_lossy().to_string();
 
     let local: DateTime<Local> = Local::now();
     epi2meitem_x.updatedAt = local.to_string();
@@ -84,21 +134,82 @@ fn epi2me_item_rebrand(epi2meitem: &Epi2MeAnalysis) -> Epi2MeAnalysis {
 }
 
 pub fn insert_untarred_desktop_analysis(
+    setup: &Epi2meSetup,
     desktop_analysis: &Epi2meDesktopAnalysis,
     temp_dir: &PathBuf,
-) {
+) -> Result<Epi2MeAnalysis, Epi4youError> {
     log::warn!("insert_untarred_desktop_analysis");
+    insert_desktop_analysis(setup, desktop_analysis, temp_dir, &[], false)
+}
 
+/// Registers an analysis in the `app.db` of `setup` and copies its files into
+/// a new instance folder.
+///
+/// File paths are relative to `temp_dir`, except for files recorded below the
+/// first folder of a `linked` pair, which are read from below the second.
+/// With `move_linked` those files are moved rather than copied.
+///
+/// The row is inserted only once the instance folder is complete, so Desktop
+/// never lists a half-copied analysis. If anything fails the instance folder
+/// is removed and moved files are put back where they came from.
+pub fn insert_desktop_analysis(
+    setup: &Epi2meSetup,
+    desktop_analysis: &Epi2meDesktopAnalysis,
+    temp_dir: &PathBuf,
+    linked: &[(PathBuf, PathBuf)],
+    move_linked: bool,
+) -> Result<Epi2MeAnalysis, Epi4youError> {
     let e2eitem = desktop_analysis.as_epi2me_analysis();
-    let epi2meitem_x = epi2me_item_rebrand(&e2eitem);
+    let epi2meitem_x = epi2me_item_rebrand(&e2eitem, setup);
     log::info!("new epi2meobj = {:?}", &epi2meitem_x);
 
-    insert_into_db(&epi2me_db::find_db().unwrap().epi2db_path, &epi2meitem_x);
+    let mut moved = Vec::new();
+    let populated = populate_instance(
+        desktop_analysis,
+        &e2eitem,
+        &epi2meitem_x,
+        temp_dir,
+        linked,
+        move_linked,
+        &mut moved,
+    )
+    .and_then(|()| resync_progress_json(&epi2meitem_x.path, &e2eitem.id, &epi2meitem_x.id))
+    .and_then(|()| insert_into_db(setup, &epi2meitem_x));
 
+    if let Err(err) = populated {
+        log::error!("import failed - removing [{}]", epi2meitem_x.path);
+        undo_moves(&moved);
+        if let Err(rm_err) = fs::remove_dir_all(&epi2meitem_x.path) {
+            log::warn!("failed to remove [{}]: {}", epi2meitem_x.path, rm_err);
+        }
+        return Err(err);
+    }
+    Ok(epi2meitem_x)
+}
+
+/// Copies, or moves, the files of an analysis into its new instance folder.
+///
+/// Every move that completed is recorded in `moved` as a source and
+/// destination pair so that it can be undone.
+fn populate_instance(
+    desktop_analysis: &Epi2meDesktopAnalysis,
+    e2eitem: &Epi2MeAnalysis,
+    epi2meitem_x: &Epi2MeAnalysis,
+    temp_dir: &PathBuf,
+    linked: &[(PathBuf, PathBuf)],
+    move_linked: bool,
+    moved: &mut Vec<(PathBuf, PathBuf)>,
+) -> Result<(), Epi4youError> {
+    let progress = Progress::new(
+        "copying",
+        desktop_analysis.files.iter().map(|file| file.size).sum(),
+    );
     for file in &desktop_analysis.files {
-        let file_to_check = PathBuf::from(temp_dir)
-            .join(&file.relative_path)
-            .join(PathBuf::from(&file.filename));
+        let name = Path::new(&file.relative_path).join(&file.filename);
+        let linked_file = epi2me_tar::linked_source(&name, linked);
+        let file_to_check = linked_file
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(temp_dir).join(&name));
 
         let mut rp = PathBuf::from(&file.relative_path);
         if rp.starts_with("instances")
@@ -106,14 +217,14 @@ pub fn insert_untarred_desktop_analysis(
             || rp.starts_with("tmp")
         {
             if rp.starts_with("instances") {
-                rp = PathBuf::from(rp.strip_prefix("instances").unwrap());
+                rp = rp.strip_prefix("instances").unwrap_or(&rp).to_path_buf();
                 let exp_dir = vec![
                     String::from(&e2eitem.workflowRepo),
                     String::from(&e2eitem.id),
                 ]
                 .join("_");
                 if rp.starts_with(&exp_dir) {
-                    rp = PathBuf::from(rp.strip_prefix(exp_dir).unwrap());
+                    rp = rp.strip_prefix(exp_dir).unwrap_or(&rp).to_path_buf();
                 }
             } else {
                 let prefix = if rp.starts_with("import_export_4you") {
@@ -121,12 +232,8 @@ pub fn insert_untarred_desktop_analysis(
                 } else {
                     "tmp"
                 };
-                rp = PathBuf::from(rp.strip_prefix(prefix).unwrap());
-                let mut components = rp.components();
-                if let Some(component) = components.next() {
-                    let c = component.as_os_str().to_str().unwrap();
-                    rp = PathBuf::from(rp.strip_prefix(c).unwrap());
-                }
+                rp = rp.strip_prefix(prefix).unwrap_or(&rp).to_path_buf();
+                rp = rp.components().skip(1).collect();
             }
         }
 
@@ -136,13 +243,62 @@ pub fn insert_untarred_desktop_analysis(
 
         if let Some(parent) = dest_file.parent() {
             if !parent.exists() {
-                let _ = fs::create_dir_all(parent);
+                fs::create_dir_all(parent).map_err(|err| {
+                    Epi4youError::FailedToCreateFolder(parent.to_path_buf(), err.into())
+                })?;
             }
         }
 
-        log::debug!("copying file [{:?}]", file_to_check);
-        let _ = fs::copy(file_to_check, dest_file);
+        if linked_file.is_some() && move_linked {
+            log::debug!("moving file [{:?}]", file_to_check);
+            move_file(&file_to_check, &dest_file, &progress)?;
+            moved.push((file_to_check, dest_file));
+        } else {
+            log::debug!("copying file [{:?}]", file_to_check);
+            copy_file(&file_to_check, &dest_file, &progress)?;
+        }
     }
+    progress.finish();
+    Ok(())
+}
+
+/// Puts moved files back, newest first; failures are logged and skipped.
+fn undo_moves(moved: &[(PathBuf, PathBuf)]) {
+    for (source, dest) in moved.iter().rev() {
+        let restored = fs::rename(dest, source)
+            .or_else(|_| fs::copy(dest, source).and_then(|_| fs::remove_file(dest)));
+        if let Err(err) = restored {
+            log::error!(
+                "failed to move [{}] back to [{}]: {}",
+                dest.display(),
+                source.display(),
+                err
+            );
+        }
+    }
+}
+
+/// Moves one file, falling back to copy and delete across filesystems.
+fn move_file(source: &Path, dest: &Path, progress: &Progress) -> Result<(), Epi4youError> {
+    if fs::rename(source, dest).is_ok() {
+        let size = fs::metadata(dest)
+            .map(|meta| meta.len())
+            .unwrap_or_default();
+        progress.inc(size);
+        return Ok(());
+    }
+    copy_file(source, dest, progress)?;
+    fs::remove_file(source)
+        .map_err(|err| Epi4youError::FailedToWritePath(source.to_path_buf(), err.into()))
+}
 
-    resync_progress_json(&epi2meitem_x.path, &e2eitem.id, &epi2meitem_x.id);
+/// Copies one file, counting the bytes read as progress.
+fn copy_file(source: &Path, dest: &Path, progress: &Progress) -> Result<(), Epi4youError> {
+    let reader = File::open(source)
+        .map_err(|err| Epi4youError::FailedToReadPath(source.to_path_buf(), err.into()))?;
+    let mut writer = File::create(dest)
+        .map_err(|err| Epi4youError::FailedToWritePath(dest.to_path_buf(), err.into()))?;
+    io::copy(&mut progress.wrap_read(reader), &mut writer)
+        .map_err(|err| Epi4youError::FailedToWritePath(dest.to_path_buf(), err.into()))?;
+    Ok(())
 }
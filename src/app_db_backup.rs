@@ -0,0 +1,206 @@
+//! Snapshots of the EPI2ME Desktop `app.db` taken before every mutation.
+//!
+//! A bad import shortly before a workshop should be reversible in seconds.
+//! Every write to `app.db` therefore first copies the live database into
+//! `import_export_4you/backups/` using SQLite's online backup API, which is
+//! safe even while another connection has the database open.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::Local;
+use rusqlite::{backup::Backup, Connection, DatabaseName, OpenFlags};
+
+use crate::{app_db_lock, epi2me_db::Epi2meSetup, epi4you_errors::Epi4youError};
+
+/// Environment variable that sets how many `app.db` backups are retained.
+pub const BACKUP_RETENTION_ENV: &str = "EPI4YOU_KEEP_BACKUPS";
+
+/// Number of backups retained when nothing else is configured.
+pub const DEFAULT_BACKUP_RETENTION: usize = 10;
+
+/// Folder below `import_export_4you` that holds the snapshots.
+const BACKUP_FOLDER: &str = "backups";
+/// Filename prefix shared by all snapshots.
+const BACKUP_PREFIX: &str = "app_";
+/// Filename suffix shared by all snapshots.
+const BACKUP_SUFFIX: &str = ".db";
+
+/// Returns the folder that holds `app.db` snapshots for this setup.
+pub fn get_backup_dir(setup: &Epi2meSetup) -> PathBuf {
+    setup.epi4you_path.join(BACKUP_FOLDER)
+}
+
+/// Snapshots `app.db` ahead of a mutation and prunes old snapshots beyond the
+/// retention of `setup`.
+///
+/// Callers take the write lock first: the snapshot is read over a separate
+/// connection, which a `BEGIN IMMEDIATE` lock still admits, while no other
+/// writer can commit between the snapshot and the mutation.
+pub fn backup_app_db(setup: &Epi2meSetup) -> Result<PathBuf, Epi4youError> {
+    let conn = Connection::open(&setup.epi2db_path)
+        .map_err(|err| Epi4youError::FailedToOpenDatabase(setup.epi2db_path.clone(), err.into()))?;
+    let target = snapshot_app_db(setup, &conn)?;
+    prune_backups(&get_backup_dir(setup), setup.keep_backups, None)?;
+    Ok(target)
+}
+
+/// Restores `app.db` from a snapshot.
+///
+/// The snapshot may be given as a bare filename from the backups folder or as
+/// a full path. The current database is itself snapshotted first so that a
+/// restore can be undone in the same way; Desktop is checked for and the
+/// database locked before that, so a refused restore leaves no snapshot and
+/// no other writer can commit between the snapshot and the restore.
+pub fn restore_app_db(setup: &Epi2meSetup, backup: &str) -> Result<PathBuf, Epi4youError> {
+    let mut source = PathBuf::from(backup);
+    if !source.exists() {
+        source = get_backup_dir(setup).join(backup);
+    }
+    if !source.exists() {
+        return Err(Epi4youError::RequiredPathMissing(source));
+    } else if source.is_dir() {
+        return Err(Epi4youError::FolderFoundWhenFileExpected(source));
+    }
+
+    let src = Connection::open_with_flags(&source, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|err| Epi4youError::FailedToOpenDatabase(source.clone(), err.into()))?;
+
+    let mut dst = app_db_lock::open_for_write(&setup.epi2db_path, setup.wait_for_desktop)?;
+    // the backup API refuses a destination inside a transaction, so the lock
+    // is held through exclusive locking mode until `dst` is closed instead
+    dst.execute_batch("PRAGMA locking_mode = EXCLUSIVE; BEGIN EXCLUSIVE; COMMIT;")
+        .map_err(|err| app_db_lock::map_write_error(&setup.epi2db_path, err))?;
+    snapshot_app_db(setup, &dst)?;
+
+    Backup::new(&src, &mut dst)
+        .and_then(|restore| restore.run_to_completion(100, Duration::from_millis(10), None))
+        .map_err(|err| app_db_lock::map_write_error(&setup.epi2db_path, err))?;
+    log::info!("app.db restored from [{}]", source.display());
+
+    // the snapshot restored from is kept even if it is now the oldest
+    drop(dst);
+    drop(src);
+    prune_backups(&get_backup_dir(setup), setup.keep_backups, Some(&source))?;
+    Ok(source)
+}
+
+/// Lists available snapshots, oldest first.
+pub fn list_backups(setup: &Epi2meSetup) -> Result<Vec<PathBuf>, Epi4youError> {
+    let backup_dir = get_backup_dir(setup);
+    if !backup_dir.exists() {
+        return Ok(Vec::new());
+    }
+    read_backups(&backup_dir)
+}
+
+/// Copies the live `app.db`, read over `conn`, into a new timestamped
+/// snapshot.
+fn snapshot_app_db(setup: &Epi2meSetup, conn: &Connection) -> Result<PathBuf, Epi4youError> {
+    let backup_dir = get_backup_dir(setup);
+    fs::create_dir_all(&backup_dir)
+        .map_err(|err| Epi4youError::FailedToCreateFolder(backup_dir.clone(), err.into()))?;
+
+    let timestamp = Local::now().format("%Y%m%dT%H%M%S%3f");
+    let target = backup_dir.join(format!("{BACKUP_PREFIX}{timestamp}{BACKUP_SUFFIX}"));
+
+    conn.backup(DatabaseName::Main, &target, None)
+        .map_err(|err| Epi4youError::FailedToQueryDatabase(err.into()))?;
+    log::info!("app.db backed up to [{}]", target.display());
+
+    Ok(target)
+}
+
+/// Deletes the oldest snapshots beyond the `keep` most recent.
+///
+/// `spare` is never deleted and does not count towards the retention.
+fn prune_backups(backup_dir: &Path, keep: usize, spare: Option<&Path>) -> Result<(), Epi4youError> {
+    // never prune the snapshot that was just taken
+    let keep = keep.max(1);
+    let spare = spare.and_then(|spare| fs::canonicalize(spare).ok());
+    let mut backups = read_backups(backup_dir)?;
+    backups.retain(|backup| spare.is_none() || fs::canonicalize(backup).ok() != spare);
+    let surplus = backups.len().saturating_sub(keep);
+    for stale in backups.drain(..surplus) {
+        log::debug!("removing stale app.db backup [{}]", stale.display());
+        fs::remove_file(&stale)
+            .map_err(|err| Epi4youError::FailedToWritePath(stale.clone(), err.into()))?;
+    }
+    Ok(())
+}
+
+/// Reads the snapshots in a backup folder, oldest first.
+fn read_backups(backup_dir: &Path) -> Result<Vec<PathBuf>, Epi4youError> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(backup_dir)
+        .map_err(|err| Epi4youError::FailedToReadPath(backup_dir.to_path_buf(), err.into()))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| is_backup_file(path))
+        .collect();
+    // timestamped names sort chronologically
+    backups.sort();
+    Ok(backups)
+}
+
+/// Returns true if the path looks like a snapshot written by this module.
+fn is_backup_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_SUFFIX))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use rusqlite::Connection;
+
+    use super::{get_backup_dir, list_backups, restore_app_db, DEFAULT_BACKUP_RETENTION};
+    use crate::{epi2me_db::Epi2meSetup, tempdir::unique_test_dir};
+
+    #[test]
+    fn restoring_the_oldest_snapshot_keeps_it() {
+        let tmp = unique_test_dir("backup");
+        let setup = Epi2meSetup {
+            epi2path: tmp.path.clone(),
+            epi2db_path: tmp.path.join("app.db"),
+            epi2wf_dir: tmp.path.join("workflows"),
+            epi4you_path: tmp.path.join("import_export_4you"),
+            instances_path: tmp.path.join("instances"),
+            arch: String::new(),
+            keep_backups: DEFAULT_BACKUP_RETENTION,
+            wait_for_desktop: None,
+        };
+        let conn = Connection::open(&setup.epi2db_path).unwrap();
+        conn.execute_batch("CREATE TABLE bs (id TEXT); INSERT INTO bs VALUES ('live');")
+            .unwrap();
+        drop(conn);
+
+        // a full backup folder whose oldest snapshot holds a different row
+        let backup_dir = get_backup_dir(&setup);
+        fs::create_dir_all(&backup_dir).unwrap();
+        for idx in 0..DEFAULT_BACKUP_RETENTION {
+            let snapshot = backup_dir.join(format!("app_20260101T0000000{idx:02}.db"));
+            let conn = Connection::open(&snapshot).unwrap();
+            conn.execute_batch(&format!(
+                "CREATE TABLE bs (id TEXT); INSERT INTO bs VALUES ('snapshot {idx}');"
+            ))
+            .unwrap();
+        }
+        let oldest = list_backups(&setup).unwrap()[0].clone();
+
+        let restored = restore_app_db(&setup, &oldest.to_string_lossy()).unwrap();
+        assert!(restored.is_file());
+        let conn = Connection::open(&setup.epi2db_path).unwrap();
+        let id: String = conn
+            .query_row("SELECT id FROM bs", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(id, "snapshot 0");
+        assert!(list_backups(&setup).unwrap().contains(&oldest));
+    }
+}
@@ -0,0 +1,127 @@
+//! Guards against writing to `app.db` underneath a running EPI2ME Desktop.
+//!
+//! Desktop keeps its own view of the database in memory and may write it back
+//! at any time, so changes made while the GUI is open can silently disappear.
+//! Before any mutation we therefore look for the Desktop process, and the write
+//! itself is performed inside a `BEGIN IMMEDIATE` transaction on a connection
+//! with a busy timeout so that a competing writer produces a clear error rather
+//! than a race.
+
+use std::{
+    path::Path,
+    process::{self, Command},
+    thread,
+    time::{Duration, Instant},
+};
+
+use rusqlite::{Connection, ErrorCode};
+
+use crate::epi4you_errors::Epi4youError;
+
+/// Executable names of EPI2ME Desktop, compared case-insensitively.
+const DESKTOP_EXECUTABLES: [&str; 2] = ["epi2me", "epi2me labs"];
+/// How long SQLite retries a locked database before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// Polling interval while waiting for Desktop to exit.
+const WAIT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Returns true if an EPI2ME Desktop process appears to be running.
+///
+/// Processes are matched on their executable name only, so a shell, editor or
+/// `epi4you` itself with `epi2me` somewhere in a path does not count; this
+/// process is skipped regardless. Detection relies on `ps`; if that is
+/// unavailable we log and carry on, as the SQLite lock still protects against
+/// concurrent writers.
+pub fn is_desktop_running() -> bool {
+    let output = match Command::new("ps").args(["-axo", "pid=,comm="]).output() {
+        Ok(output) if output.status.success() => output,
+        _ => {
+            log::warn!("unable to list processes - cannot check for EPI2ME Desktop");
+            return false;
+        }
+    };
+
+    let own_pid = process::id().to_string();
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().split_once(char::is_whitespace))
+        .any(|(pid, comm)| pid != own_pid && is_desktop_executable(comm))
+}
+
+/// Returns true if the `comm` reported by `ps`, a bare name or a full path on
+/// macOS, is the Desktop executable.
+fn is_desktop_executable(comm: &str) -> bool {
+    let name = Path::new(comm.trim())
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+    DESKTOP_EXECUTABLES.contains(&name)
+}
+
+/// Refuses, or waits for up to `wait`, while EPI2ME Desktop is running.
+pub fn ensure_desktop_stopped(wait: Option<Duration>) -> Result<(), Epi4youError> {
+    if !is_desktop_running() {
+        return Ok(());
+    }
+
+    let Some(timeout) = wait else {
+        log::error!("EPI2ME Desktop is running - close it first or use `--wait`");
+        return Err(Epi4youError::Epi2meDesktopIsRunning);
+    };
+
+    log::warn!(
+        "EPI2ME Desktop is running - waiting up to {}s for it to exit ...",
+        timeout.as_secs()
+    );
+    let deadline = Instant::now() + timeout;
+    while is_desktop_running() {
+        let now = Instant::now();
+        if now >= deadline {
+            log::error!("EPI2ME Desktop did not exit within {}s", timeout.as_secs());
+            return Err(Epi4youError::Epi2meDesktopStillRunning(timeout.as_secs()));
+        }
+        thread::sleep(WAIT_INTERVAL.min(deadline - now));
+    }
+    log::info!("EPI2ME Desktop has exited");
+    Ok(())
+}
+
+/// Opens `app.db` for writing once Desktop is confirmed to be closed, waiting
+/// for up to `wait` for it to exit.
+pub fn open_for_write(path: &Path, wait: Option<Duration>) -> Result<Connection, Epi4youError> {
+    ensure_desktop_stopped(wait)?;
+
+    let conn = Connection::open(path)
+        .map_err(|err| Epi4youError::FailedToOpenDatabase(path.to_path_buf(), err.into()))?;
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .map_err(|err| Epi4youError::FailedToQueryDatabase(err.into()))?;
+    Ok(conn)
+}
+
+/// Maps a SQLite failure to a lock error where the database was busy.
+pub fn map_write_error(path: &Path, err: rusqlite::Error) -> Epi4youError {
+    match err.sqlite_error_code() {
+        Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => {
+            log::error!("[{}] is locked by another writer", path.display());
+            Epi4youError::DatabaseIsLocked(path.to_path_buf())
+        }
+        _ => Epi4youError::FailedToQueryDatabase(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_desktop_executable;
+
+    #[test]
+    fn matches_only_the_desktop_executable() {
+        assert!(is_desktop_executable("epi2me"));
+        assert!(is_desktop_executable(
+            "/Applications/EPI2ME.app/Contents/MacOS/EPI2ME"
+        ));
+        assert!(!is_desktop_executable("/home/trainee/epi2me/bin/epi4you"));
+        assert!(!is_desktop_executable("epi4you"));
+        assert!(!is_desktop_executable("vim"));
+    }
+}
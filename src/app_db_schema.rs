@@ -0,0 +1,251 @@
+//! Schema detection for the EPI2ME Desktop `app.db`.
+//!
+//! Desktop releases add columns to the `bs` analysis table over time. Rather
+//! than hard-coding one release's layout, the table is introspected with
+//! `PRAGMA table_info` and each analysis field is mapped onto whichever columns
+//! exist. Columns that `epi4you` knows nothing about are filled with a neutral
+//! value where SQLite requires one, and the insert is refused when no such
+//! value can be chosen safely.
+
+use rusqlite::{types::Value, Connection};
+
+use crate::{app_db::Epi2MeAnalysis, epi4you_errors::Epi4youError};
+
+/// Schema of the `bs` table as created by `epi4you init`.
+pub const BS_TABLE_SCHEMA: &str = "CREATE TABLE IF NOT EXISTS bs (
+    id VARCHAR(255) UNIQUE PRIMARY KEY,
+    path VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    status VARCHAR(255) NOT NULL,
+    workflowRepo VARCHAR(255) NOT NULL,
+    workflowUser VARCHAR(255) NOT NULL,
+    workflowCommit VARCHAR(255),
+    workflowVersion VARCHAR(255) NOT NULL,
+    createdAt DATETIME NOT NULL,
+    updatedAt DATETIME NOT NULL
+)";
+
+/// One column of the `bs` table as reported by `PRAGMA table_info`.
+#[derive(Debug, Clone)]
+pub struct BsColumn {
+    pub name: String,
+    pub declared_type: String,
+    pub not_null: bool,
+    pub has_default: bool,
+}
+
+/// Column names and values for a single `INSERT INTO bs`.
+pub struct BsInsert {
+    pub columns: Vec<String>,
+    pub values: Vec<Value>,
+}
+
+impl BsInsert {
+    /// Renders the parameterised SQL statement for this insert.
+    ///
+    /// Column names come from the live table, so each is quoted in case a
+    /// later Desktop release names one like a keyword.
+    pub fn get_sql(&self) -> String {
+        let columns: Vec<String> = self.columns.iter().map(|name| quote_ident(name)).collect();
+        let placeholders: Vec<String> = (1..=self.columns.len())
+            .map(|idx| format!("?{idx}"))
+            .collect();
+        format!(
+            "INSERT INTO bs ({}) VALUES ({})",
+            columns.join(", "),
+            placeholders.join(", ")
+        )
+    }
+}
+
+/// Quotes an SQL identifier, doubling any embedded quote.
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Reads the live layout of the `bs` table.
+pub fn read_bs_columns(conn: &Connection) -> Result<Vec<BsColumn>, Epi4youError> {
+    let mut stmt = conn
+        .prepare("PRAGMA table_info(bs)")
+        .map_err(|err| Epi4youError::FailedToQueryDatabase(err.into()))?;
+    let columns = stmt
+        .query_map([], |row| {
+            Ok(BsColumn {
+                name: row.get(1)?,
+                declared_type: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                not_null: row.get::<_, i64>(3)? != 0,
+                has_default: row.get::<_, Option<String>>(4)?.is_some(),
+            })
+        })
+        .and_then(|rows| rows.collect::<Result<Vec<BsColumn>, _>>())
+        .map_err(|err| Epi4youError::FailedToQueryDatabase(err.into()))?;
+
+    if columns.is_empty() {
+        log::error!("app.db does not contain a `bs` table");
+        return Err(Epi4youError::UnsupportedDatabaseSchema(String::from("bs")));
+    }
+    Ok(columns)
+}
+
+/// Maps an analysis onto whichever `bs` columns the database actually has.
+///
+/// Known fields are written to their matching columns. Unknown columns are
+/// skipped when SQLite can fill them itself (nullable or defaulted), and are
+/// otherwise given a neutral value derived from the declared type.
+pub fn plan_bs_insert(
+    columns: &[BsColumn],
+    epi2meitem: &Epi2MeAnalysis,
+) -> Result<BsInsert, Epi4youError> {
+    if !columns.iter().any(|column| column.name == "id") {
+        log::error!("the `bs` table has no `id` column - unrecognised app.db");
+        return Err(Epi4youError::UnsupportedDatabaseSchema(String::from("id")));
+    }
+
+    let mut insert = BsInsert {
+        columns: Vec::new(),
+        values: Vec::new(),
+    };
+
+    for column in columns {
+        let value = if let Some(value) = epi2meitem.get_field(&column.name) {
+            Value::Text(value.clone())
+        } else if !column.not_null || column.has_default {
+            log::debug!("leaving optional `bs` column [{}] unset", column.name);
+            continue;
+        } else if let Some(value) = get_neutral_value(column, epi2meitem) {
+            log::warn!(
+                "`bs` column [{}] is unknown to epi4you - using [{:?}]",
+                column.name,
+                value
+            );
+            value
+        } else {
+            log::error!(
+                "cannot satisfy required `bs` column [{}] of type [{}]",
+                column.name,
+                column.declared_type
+            );
+            return Err(Epi4youError::UnsupportedDatabaseSchema(column.name.clone()));
+        };
+
+        insert.columns.push(column.name.clone());
+        insert.values.push(value);
+    }
+
+    for field in Epi2MeAnalysis::FIELDS {
+        if !insert.columns.iter().any(|column| column == field) {
+            log::warn!("app.db has no `bs` column for [{field}] - value dropped");
+        }
+    }
+
+    Ok(insert)
+}
+
+/// Chooses a neutral value for a required column based on its declared type.
+///
+/// The rules loosely follow SQLite's type affinity, with timestamps reusing the
+/// analysis creation time so date-sorted views in the GUI still behave.
+fn get_neutral_value(column: &BsColumn, epi2meitem: &Epi2MeAnalysis) -> Option<Value> {
+    let declared = column.declared_type.to_uppercase();
+
+    if declared.contains("INT") || declared.contains("BOOL") {
+        Some(Value::Integer(0))
+    } else if declared.contains("CHAR") || declared.contains("CLOB") || declared.contains("TEXT") {
+        Some(Value::Text(String::new()))
+    } else if declared.contains("REAL") || declared.contains("FLOA") || declared.contains("DOUB") {
+        Some(Value::Real(0.0))
+    } else if declared.contains("DATE") || declared.contains("TIME") {
+        Some(Value::Text(epi2meitem.createdAt.clone()))
+    } else if declared.contains("JSON") {
+        Some(Value::Text(String::from("{}")))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{plan_bs_insert, read_bs_columns, BS_TABLE_SCHEMA};
+    use crate::app_db::Epi2MeAnalysis;
+    use rusqlite::{params_from_iter, Connection};
+
+    fn analysis() -> Epi2MeAnalysis {
+        Epi2MeAnalysis {
+            id: String::from("01HBWYY322RMWACRMGX70BMMPB"),
+            path: String::from("/tmp/instances/wf-clone-validation_01HBWYY322RMWACRMGX70BMMPB"),
+            name: String::from("kind_curie"),
+            status: String::from("COMPLETED"),
+            workflowRepo: String::from("wf-clone-validation"),
+            workflowUser: String::from("epi2me-labs"),
+            workflowCommit: String::from("123abc"),
+            workflowVersion: String::from("v1.2.3"),
+            createdAt: String::from("2023-10-04 08:55:05.680 +00:00"),
+            updatedAt: String::from("2023-10-04 08:55:05.680 +00:00"),
+        }
+    }
+
+    #[test]
+    fn maps_all_fields_onto_reference_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(BS_TABLE_SCHEMA, []).unwrap();
+
+        let columns = read_bs_columns(&conn).unwrap();
+        let insert = plan_bs_insert(&columns, &analysis()).unwrap();
+        assert_eq!(insert.columns.len(), 10);
+
+        conn.execute(&insert.get_sql(), params_from_iter(insert.values))
+            .unwrap();
+    }
+
+    #[test]
+    fn fills_unknown_required_columns_and_drops_missing_ones() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE bs (id VARCHAR(255) PRIMARY KEY, path VARCHAR(255) NOT NULL, \
+             name VARCHAR(255) NOT NULL, hidden TINYINT(1) NOT NULL, \
+             startedAt DATETIME NOT NULL, notes TEXT, theme TEXT NOT NULL DEFAULT 'x')",
+            [],
+        )
+        .unwrap();
+
+        let columns = read_bs_columns(&conn).unwrap();
+        let insert = plan_bs_insert(&columns, &analysis()).unwrap();
+        assert_eq!(
+            insert.columns,
+            vec!["id", "path", "name", "hidden", "startedAt"]
+        );
+
+        conn.execute(&insert.get_sql(), params_from_iter(insert.values))
+            .unwrap();
+    }
+
+    #[test]
+    fn quotes_column_names() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            r#"CREATE TABLE bs (id VARCHAR(255) PRIMARY KEY, "order" INTEGER NOT NULL, "odd ""name""" TEXT NOT NULL)"#,
+            [],
+        )
+        .unwrap();
+
+        let columns = read_bs_columns(&conn).unwrap();
+        let insert = plan_bs_insert(&columns, &analysis()).unwrap();
+        assert_eq!(insert.columns, vec!["id", "order", "odd \"name\""]);
+
+        conn.execute(&insert.get_sql(), params_from_iter(insert.values))
+            .unwrap();
+    }
+
+    #[test]
+    fn refuses_required_column_without_neutral_value() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE bs (id VARCHAR(255) PRIMARY KEY, thumbnail BLOB NOT NULL)",
+            [],
+        )
+        .unwrap();
+
+        let columns = read_bs_columns(&conn).unwrap();
+        assert!(plan_bs_insert(&columns, &analysis()).is_err());
+    }
+}
@@ -1,36 +1,74 @@
-use std::path::PathBuf;
+use std::path::{self, PathBuf};
 
-use crate::epi2me_db::{self};
+use crate::app_db::{self, Epi2MeAnalysis};
+use crate::epi2me_db::Epi2meSetup;
 use crate::epi2me_desktop_analysis::Epi2meDesktopAnalysis;
 use crate::epi2me_tar;
 use crate::epi2me_workflow::get_relative_path;
+use crate::epi4you_errors::Epi4youError;
 use crate::tempdir::TempDir;
 
 use crate::xmanifest::{Epi2MeContent, FileManifest};
 use crate::xmanifest::{Epi2MeManifest, MANIFEST_JSON};
 
+/// Inventories a staged CLI run and its output folder into `vehicle`.
+///
+/// Returns the folder, relative to `local_prefix`, that the outputs are
+/// recorded under: the `output/` folder of the staged analysis.
+fn inventory_cli_run(
+    vehicle: &mut Epi2meDesktopAnalysis,
+    source: &PathBuf,
+    outputs: &PathBuf,
+    local_prefix: &PathBuf,
+) -> Result<PathBuf, Epi4youError> {
+    vehicle.fish_files(source, local_prefix)?;
+    let archive_outputs = get_relative_path(&source.join("output"), local_prefix)?;
+    vehicle.fish_linked_files(outputs, &archive_outputs)?;
+    Ok(archive_outputs)
+}
+
+/// Registers a staged CLI run in the local Desktop without an archive.
+///
+/// The staged files are copied into a new instance folder and the output
+/// folder's files copied, or moved with `move_outputs`, after them.
+pub fn import_cli_run(
+    setup: &Epi2meSetup,
+    mut vehicle: Epi2meDesktopAnalysis,
+    source: PathBuf,
+    outputs: PathBuf,
+    move_outputs: bool,
+) -> Result<Epi2MeAnalysis, Epi4youError> {
+    let archive_outputs = inventory_cli_run(&mut vehicle, &source, &outputs, &setup.epi2path)?;
+    app_db::insert_desktop_analysis(
+        setup,
+        &vehicle,
+        &setup.epi2path,
+        &[(archive_outputs, outputs)],
+        move_outputs,
+    )
+}
+
+/// Archives a staged CLI run together with its output folder.
+///
+/// `source` holds the generated files; `outputs` is tarred from where it is
+/// and appears as the `output/` folder of the analysis. Paths are recorded
+/// relative to the root of `setup`, or to `/` without one.
 pub fn export_cli_run(
-    ulidstr: &String,
+    setup: Option<&Epi2meSetup>,
+    mut vehicle: Epi2meDesktopAnalysis,
     source: PathBuf,
+    outputs: PathBuf,
     temp_dir: TempDir,
     dest: PathBuf,
-    nextflow_stdout: &String,
-    timestamp: &String,
     force: &bool,
-) {
-    let epi2db = epi2me_db::find_db();
-    let mut local_prefix = PathBuf::from("/");
-    if epi2db.is_some() {
-        local_prefix = epi2db.unwrap().epi2path;
-    }
+) -> Result<Epi2MeManifest, Epi4youError> {
+    let local_prefix = setup.map_or_else(|| PathBuf::from("/"), |setup| setup.epi2path.clone());
 
     let mut manifest = Epi2MeManifest::new(temp_dir.path.clone());
     let mut all_files: Vec<FileManifest> = Vec::new();
 
     log::info!("packing [{:?}] into .2me format archive", &source.clone());
 
-    let mut vehicle = Epi2meDesktopAnalysis::init(ulidstr, &source, nextflow_stdout, timestamp);
-
     /* we need to parse some information here - at least the tuple of user//repo */
 
     manifest.note_packaged_analysis(
@@ -43,13 +81,18 @@ pub fn export_cli_run(
     );
 
     // as per https://github.com/sagrudd/epi4you/issues/1 - ensure that destination is not in source
-    let common_prefix = &dest.strip_prefix(&source);
-    if !common_prefix.is_err() {
-        eprintln!("Destination is a child of source - this will not work!");
-        return;
+    let dest = path::absolute(&dest)
+        .map_err(|err| Epi4youError::FailedToWritePath(dest.clone(), err.into()))?;
+    let source = path::absolute(&source)
+        .map_err(|err| Epi4youError::FailedToReadPath(source.clone(), err.into()))?;
+    let outputs = path::absolute(&outputs)
+        .map_err(|err| Epi4youError::FailedToReadPath(outputs.clone(), err.into()))?;
+    if dest.starts_with(&source) || dest.starts_with(&outputs) {
+        log::error!("Destination is a child of source - this will not work!");
+        return Err(Epi4youError::DestinationInsideSource(dest));
     }
 
-    vehicle.fish_files(&source, &local_prefix);
+    let archive_outputs = inventory_cli_run(&mut vehicle, &source, &outputs, &local_prefix)?;
 
     all_files.extend(vehicle.get_files());
     manifest.filecount += u64::try_from(vehicle.get_files().len()).unwrap();
@@ -58,22 +101,78 @@ pub fn export_cli_run(
         .payload
         .push(Epi2MeContent::Epi2mePayload(vehicle.clone()));
 
-    println!("{:?}", &manifest);
+    log::debug!("{:?}", &manifest);
 
     let mut manifest_pb = PathBuf::from(&temp_dir.path);
     manifest_pb.push(MANIFEST_JSON);
-    manifest.write(&manifest_pb);
+    manifest.write(&manifest_pb)?;
 
     // tar up the contents specified in the manifest
     if dest.exists() && !*force {
-        eprintln!("destination archive already exists - cannot continue without `--force`")
-    } else {
-        // tar up the contents specified in the manifest
-        epi2me_tar::tar(
-            None,
-            dest,
-            &all_files,
-            &get_relative_path(&manifest_pb, &local_prefix),
+        log::error!("destination archive already exists - cannot continue without `--force`");
+        return Err(Epi4youError::FileAlreadyExistsUnforcedExecution(dest));
+    }
+
+    // tar up the contents specified in the manifest
+    epi2me_tar::tar(
+        &local_prefix,
+        dest,
+        &all_files,
+        &get_relative_path(&manifest_pb, &local_prefix)?,
+        &[(archive_outputs, outputs)],
+    )?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use super::export_cli_run;
+    use crate::{
+        epi2me_db::Epi2meSetup,
+        epi2me_desktop_analysis::Epi2meDesktopAnalysis,
+        tempdir::{self, unique_test_dir},
+    };
+
+    #[test]
+    fn bundles_with_a_relative_root() {
+        let tmp = unique_test_dir("bundle");
+        let root = tmp.path.join("epi2me");
+        fs::create_dir_all(root.join("workflows")).unwrap();
+        fs::write(root.join("app.db"), "").unwrap();
+        let outputs = tmp.path.join("output");
+        fs::create_dir_all(&outputs).unwrap();
+        fs::write(outputs.join("report.html"), "report").unwrap();
+
+        // the same folder, named relative to the working directory
+        let cwd = env::current_dir().unwrap();
+        let mut relative: PathBuf = cwd.components().skip(1).map(|_| "..").collect();
+        relative.push(root.strip_prefix("/").unwrap());
+        let setup = Epi2meSetup::from_root(&relative).unwrap();
+        assert!(setup.epi2path.is_absolute());
+
+        let temp_dir = tempdir::get_tempdir(Some(&setup)).unwrap();
+        fs::write(temp_dir.path.join("nextflow.stdout"), "stdout").unwrap();
+        let vehicle = Epi2meDesktopAnalysis::init(
+            &String::from("01ABC"),
+            &temp_dir.path,
+            &String::new(),
+            &String::from("2026-10-19 10:00:00"),
+            "COMPLETED",
         );
+        let dest = tmp.path.join("run.2me.tar");
+        let manifest = export_cli_run(
+            Some(&setup),
+            vehicle,
+            temp_dir.path.clone(),
+            outputs,
+            temp_dir,
+            dest.clone(),
+            &false,
+        )
+        .unwrap();
+        assert_eq!(manifest.filecount, 2);
+        assert!(dest.is_file());
     }
 }
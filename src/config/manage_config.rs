@@ -0,0 +1,38 @@
+//! CLI entry point for inspecting `epi4you.toml` configuration.
+//!
+//! Defaults can come from a user file, a project file, environment variables
+//! or the built-in values, which makes it hard to tell why a run picked up a
+//! particular Nextflow binary. `config show` answers that question.
+
+use clap::{ArgMatches, Command};
+
+use epi4you::Epi4youError;
+
+use crate::{epi4you_config::Config, output};
+
+/// CLI subcommand name for configuration housekeeping.
+pub const CONFIG: &str = "config";
+/// Nested subcommand printing the effective configuration.
+const SHOW: &str = "show";
+
+/// Returns the clap configuration for the config subcommand.
+pub fn get_cli_setup() -> Command {
+    Command::new(CONFIG)
+        .about("inspect epi4you.toml configuration")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new(SHOW)
+                .about("print the effective configuration and where each value came from"),
+        )
+}
+
+/// Executes the configuration housekeeping flow.
+pub fn process_config_command(args: &ArgMatches, config: &Config) -> Result<(), Epi4youError> {
+    match args.subcommand() {
+        Some((SHOW, _)) => {
+            output::emit(&config.entries());
+            Ok(())
+        }
+        _ => Err(Epi4youError::AdditionalParameterRequired),
+    }
+}
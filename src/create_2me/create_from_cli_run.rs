@@ -5,23 +5,150 @@
 //! Nextflow and only later want the portability or GUI import experience that
 //! EPI2ME Desktop provides.
 
-use std::{env, path::PathBuf};
+use std::{env, path::PathBuf, thread, time::Duration};
 
 use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+
+use epi4you::{
+    nextflow::{nextflow_watch::RunWatcher, nextflow_work::WorkMode},
+    xmanifest::Epi2MeContent,
+    BundleOptions, Epi2MeManifest, Epi4youError, NextFlowResultFolder, ResourceSummary,
+};
 
 use crate::{
-    epi4you_errors::Epi4youError, nextflow::nextflow_toolkit::NextFlowResultFolder,
-    tempdir::TempDir,
+    dataframe::{self, nextflow_vec_to_df},
+    epi2me_options::Epi2meOptions,
+    epi4you_config::Config,
+    output::{self, OutputFormat},
 };
 
 /// CLI subcommand name for packaging CLI Nextflow runs.
 pub const NEXTFLOW_RUN: &str = "nextflow-run";
+/// Seconds between rescans in `--watch` mode unless `--interval` is given.
+const DEFAULT_WATCH_INTERVAL: u64 = 60;
+/// Options selecting a single run, which `--watch` and its options reject.
+///
+/// Listed on the watch options too, because clap waives `requires("watch")`
+/// when an argument conflicting with `--watch` is present.
+const WATCH_CONFLICTS: [&str; 6] = [
+    "list",
+    "runid",
+    "twome",
+    "into-desktop",
+    "out-dir",
+    "nxf-log",
+];
+
+/// One row of the `--timeline` table.
+#[derive(Serialize)]
+struct TimelineRow {
+    time: String,
+    event: &'static str,
+    detail: String,
+}
+
+/// One row of the `--resources` table.
+#[derive(Serialize)]
+struct ResourceRow {
+    run_name: String,
+    status: String,
+    tasks: Option<usize>,
+    cpu_hours: Option<f64>,
+    peak_rss_bytes: Option<u64>,
+    wall_time_seconds: Option<u64>,
+    read_bytes: Option<u64>,
+    written_bytes: Option<u64>,
+    heaviest_process: Option<String>,
+}
+
+impl ResourceRow {
+    /// Flattens the summary of `run_name`; a run without a trace has empty cells.
+    fn new(run_name: &str, status: &str, summary: Option<&ResourceSummary>) -> Self {
+        ResourceRow {
+            run_name: run_name.trim().to_owned(),
+            status: status.trim().to_owned(),
+            tasks: summary.map(|summary| summary.tasks),
+            cpu_hours: summary.map(|summary| summary.cpu_hours),
+            peak_rss_bytes: summary.and_then(|summary| summary.peak_rss_bytes),
+            wall_time_seconds: summary.and_then(|summary| summary.wall_time_seconds),
+            read_bytes: summary.map(|summary| summary.read_bytes),
+            written_bytes: summary.map(|summary| summary.written_bytes),
+            heaviest_process: summary.and_then(|summary| {
+                summary
+                    .heaviest_processes
+                    .first()
+                    .map(|usage| usage.process.clone())
+            }),
+        }
+    }
+}
+
+/// Result of bundling one CLI run.
+#[derive(Serialize)]
+struct BundleSummary {
+    archive: PathBuf,
+    run_name: String,
+    id: String,
+    workflow: String,
+    recognised_by_desktop: bool,
+    filecount: u64,
+    files_size: u64,
+    output_dir: String,
+    output_dir_strategy: String,
+    work_tasks: usize,
+}
+
+impl BundleSummary {
+    /// Summarises the manifest written into `archive` for `run_name`.
+    fn from_manifest(archive: PathBuf, run_name: String, manifest: &Epi2MeManifest) -> Self {
+        let analysis = manifest.payload.iter().find_map(|content| match content {
+            Epi2MeContent::Epi2mePayload(analysis) => Some(analysis),
+            _ => None,
+        });
+        let execution = analysis.and_then(|analysis| analysis.execution.as_deref());
+        BundleSummary {
+            archive,
+            run_name,
+            id: analysis
+                .map(|analysis| analysis.id.clone())
+                .unwrap_or_default(),
+            workflow: analysis
+                .map(|analysis| format!("{}/{}", analysis.workflowUser, analysis.workflowRepo))
+                .unwrap_or_default(),
+            recognised_by_desktop: execution
+                .and_then(|execution| execution.workflow.as_ref())
+                .is_some_and(|workflow| workflow.recognised_by_desktop),
+            filecount: manifest.filecount,
+            files_size: manifest.files_size,
+            output_dir: execution
+                .and_then(|execution| execution.output_dir.clone())
+                .unwrap_or_default(),
+            output_dir_strategy: execution
+                .and_then(|execution| execution.output_dir_strategy)
+                .map(|strategy| strategy.to_string())
+                .unwrap_or_default(),
+            work_tasks: execution.map_or(0, |execution| {
+                execution
+                    .work_tasks
+                    .iter()
+                    .filter(|task| task.bundled.is_some())
+                    .count()
+            }),
+        }
+    }
+}
 
 /// Returns the clap configuration for the CLI-run capture command.
 pub fn get_cli_setup() -> Command {
     let my_command = Command::new(NEXTFLOW_RUN)
         .about("create 2me from nextflow cli results")
+        .visible_alias("nextflow")
         .arg(arg!(--list "List analyses run using Nextflow CLI").action(ArgAction::SetTrue))
+        .arg(
+            arg!(--timeline "show the events recorded in the run's .nextflow.log")
+                .action(ArgAction::SetTrue),
+        )
         .arg(
             arg!(--nxf_bin "path to nextflow binary (if not obvious)")
                 .action(ArgAction::Set)
@@ -46,7 +173,65 @@ pub fn get_cli_setup() -> Command {
                 .required(false)
                 .value_parser(value_parser!(String)),
         )
-        .arg(arg!(--force "force overwrite of exising twome archive").action(ArgAction::SetTrue));
+        .arg(arg!(--force "force overwrite of exising twome archive").action(ArgAction::SetTrue))
+        .arg(
+            arg!(--"include-failed" "allow bundling runs that failed or were cancelled")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"out-dir" "output folder of the run, if it cannot be found")
+                .action(ArgAction::Set)
+                .required(false)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"nxf-log" "the run's .nextflow.log file, if another one is picked")
+                .action(ArgAction::Set)
+                .required(false)
+                .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(--"with-work" <mode> "bundle task work folders: none, failed-tasks, all or process=<name>")
+                .required(false)
+                .value_parser(value_parser!(WorkMode)),
+        )
+        .arg(
+            arg!(--"with-work-outputs" "also bundle the files tasks wrote into their work folders")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--resources "summarise CPU, memory, time and I/O from the execution trace")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"into-desktop" "import the run into the local EPI2ME Desktop instead of writing an archive")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--"move-outputs" "with --into-desktop, move the run's outputs instead of copying them")
+                .action(ArgAction::SetTrue)
+                .requires("into-desktop"),
+        )
+        .arg(
+            arg!(--watch <dir> "keep bundling runs as they finish in this launch folder")
+                .required(false)
+                .value_parser(value_parser!(PathBuf))
+                .conflicts_with_all(WATCH_CONFLICTS),
+        )
+        .arg(
+            arg!(--"watch-dest" <dir> "folder that --watch writes archives into")
+                .required(false)
+                .value_parser(value_parser!(PathBuf))
+                .requires("watch")
+                .conflicts_with_all(WATCH_CONFLICTS),
+        )
+        .arg(
+            arg!(--interval <seconds> "seconds between --watch rescans")
+                .required(false)
+                .value_parser(value_parser!(u64).range(1..))
+                .requires("watch")
+                .conflicts_with_all(WATCH_CONFLICTS),
+        );
     return my_command;
 }
 
@@ -54,18 +239,39 @@ pub fn get_cli_setup() -> Command {
 ///
 /// Depending on the arguments this either:
 ///
-/// - lists successful runs discovered via `nextflow log`, or
+/// - keeps bundling runs as they finish in a watched folder,
+/// - lists the runs found in the folder's history, with their status or
+///   resource usage,
+/// - shows the event timeline or resource usage of one run,
+/// - imports one selected run straight into the local Desktop, or
 /// - packages one selected run into a `.2me` archive.
 pub fn process_clicapture_command(
     args: &ArgMatches,
-    tempdir: &TempDir,
+    config: &Config,
+    epi2me: &Epi2meOptions,
 ) -> Result<(), Epi4youError> {
-    let nxf_bin = args.get_one::<String>("nxf_bin").cloned();
-    let nxf_work = args.get_one::<String>("nxf_work").cloned();
+    let nxf_bin = args
+        .get_one::<String>("nxf_bin")
+        .cloned()
+        .or_else(|| config.nxf_bin());
+    let nxf_work = args
+        .get_one::<String>("nxf_work")
+        .cloned()
+        .or_else(|| config.nxf_work());
     let runid = args.get_one::<String>("runid").cloned();
     let twome = args.get_one::<String>("twome").cloned();
     let list = args.get_one::<bool>("list").copied().unwrap_or(false);
-    let force = args.get_one::<bool>("force").copied().unwrap_or(false);
+    let options = BundleOptions {
+        force: args.get_flag("force") || config.force(),
+        include_failed: args.get_flag("include-failed"),
+        out_dir: args.get_one::<PathBuf>("out-dir").cloned(),
+        nxf_log: args.get_one::<PathBuf>("nxf-log").cloned(),
+        with_work: args
+            .get_one::<WorkMode>("with-work")
+            .cloned()
+            .unwrap_or_default(),
+        work_outputs: args.get_flag("with-work-outputs"),
+    };
 
     let mut nxf_workdir = nxf_work.clone();
     if nxf_workdir.is_none() {
@@ -78,16 +284,124 @@ pub fn process_clicapture_command(
         );
     }
 
-    let nextflow_run_folder =
-        NextFlowResultFolder::init(PathBuf::from(nxf_workdir.clone().unwrap()), nxf_bin)?;
+    let nxf_workdir = PathBuf::from(nxf_workdir.unwrap_or_default());
 
-    if list {
-        nextflow_run_folder.list_runs();
+    if let Some(watch) = args.get_one::<PathBuf>("watch") {
+        let out = args
+            .get_one::<PathBuf>("watch-dest")
+            .cloned()
+            .or_else(|| config.twome_dir())
+            .ok_or(Epi4youError::AdditionalParameterRequired)?;
+        let interval = args
+            .get_one::<u64>("interval")
+            .copied()
+            .unwrap_or(DEFAULT_WATCH_INTERVAL);
+        let mut watcher = RunWatcher::init(watch.clone(), nxf_bin, epi2me.find(), out)?;
+        loop {
+            match watcher.scan(&options) {
+                Ok(bundled) => {
+                    for (run_name, archive, manifest) in bundled {
+                        output::emit(&BundleSummary::from_manifest(archive, run_name, &manifest));
+                    }
+                }
+                Err(err) => log::warn!("unable to scan [{}] - {err}", watch.display()),
+            }
+            thread::sleep(Duration::from_secs(interval));
+        }
+    } else if list {
+        let nextflow_run_folder = NextFlowResultFolder::init(nxf_workdir, nxf_bin)?;
+        if args.get_flag("resources") {
+            let rows: Vec<ResourceRow> = nextflow_run_folder
+                .get_runs()
+                .iter()
+                .map(|run| {
+                    let summary = nextflow_run_folder
+                        .resources(run, &BundleOptions::default())
+                        .unwrap_or_else(|err| {
+                            log::warn!("unable to read resources of [{}] - {err}", run.run_name);
+                            None
+                        });
+                    ResourceRow::new(&run.run_name, &run.status, summary.as_ref())
+                })
+                .collect();
+            output::emit(&rows);
+        } else if output::get_output_format() == OutputFormat::Table {
+            let df = nextflow_vec_to_df(nextflow_run_folder.get_runs().to_vec());
+            dataframe::print_polars_df(&df);
+        } else {
+            output::emit(&nextflow_run_folder.get_runs());
+        }
+    } else if args.get_flag("timeline") {
+        let runid = runid.ok_or(Epi4youError::AdditionalParameterRequired)?;
+        let timeline =
+            epi4you::nextflow_timeline(nxf_workdir, nxf_bin, &runid, options.nxf_log.as_deref())?;
+        if output::get_output_format() == OutputFormat::Json {
+            output::emit(&timeline.events);
+        } else {
+            let rows: Vec<TimelineRow> = timeline
+                .events
+                .iter()
+                .map(|entry| TimelineRow {
+                    time: entry.timestamp.clone().unwrap_or_default(),
+                    event: entry.event.kind(),
+                    detail: entry.event.detail(),
+                })
+                .collect();
+            output::emit(&rows);
+        }
+    } else if args.get_flag("resources") {
+        let runid = runid.ok_or(Epi4youError::AdditionalParameterRequired)?;
+        let nextflow_run_folder = NextFlowResultFolder::init(nxf_workdir, nxf_bin)?;
+        let run = nextflow_run_folder.verify_cli_entity(runid.clone())?;
+        let summary = nextflow_run_folder
+            .resources(&run, &options)?
+            .ok_or(Epi4youError::ExecutionTraceNotFound(runid))?;
+        if output::get_output_format() == OutputFormat::Json {
+            output::emit(&summary);
+        } else {
+            output::emit(&ResourceRow::new(
+                &run.run_name,
+                &run.status,
+                Some(&summary),
+            ));
+        }
+    } else if args.get_flag("into-desktop") {
+        let runid = runid.ok_or(Epi4youError::AdditionalParameterRequired)?;
+        let analysis = epi4you::import_nextflow_run(
+            &epi2me.require()?,
+            nxf_workdir,
+            nxf_bin,
+            &runid,
+            &options,
+            args.get_flag("move-outputs"),
+        )?;
+        output::emit(&analysis);
     } else {
         let runid = runid.ok_or(Epi4youError::AdditionalParameterRequired)?;
-        let twome = twome.ok_or(Epi4youError::AdditionalParameterRequired)?;
-        let wf_analysis = nextflow_run_folder.verify_cli_entity(runid)?;
-        nextflow_run_folder.bundle_cli_run(tempdir, wf_analysis, &twome, &force)?;
+        // without --twome the archive is named after the run in `twome_dir`
+        let twome = twome
+            .or_else(|| {
+                config.twome_dir().map(|dir| {
+                    dir.join(format!("{runid}.2me.tar"))
+                        .to_string_lossy()
+                        .into_owned()
+                })
+            })
+            .ok_or(Epi4youError::AdditionalParameterRequired)?;
+        let manifest = epi4you::export_nextflow_run(
+            epi2me.find().as_ref(),
+            nxf_workdir,
+            nxf_bin,
+            &runid,
+            &twome,
+            &options,
+        )?;
+
+        output::emit(&BundleSummary::from_manifest(
+            PathBuf::from(twome),
+            runid,
+            &manifest,
+        ));
     }
 
     Ok(())
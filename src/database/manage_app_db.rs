@@ -0,0 +1,76 @@
+//! CLI entry point for housekeeping on the EPI2ME Desktop `app.db`.
+//!
+//! Every mutation `epi4you` performs on `app.db` is preceded by a snapshot.
+//! This command exposes those snapshots so that an unwanted change can be
+//! rolled back without reaching for SQLite tooling.
+
+use std::path::PathBuf;
+
+use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+
+use epi4you::{app_db_backup, Epi4youError};
+
+use crate::{epi2me_options::Epi2meOptions, output};
+
+/// CLI subcommand name for `app.db` housekeeping.
+pub const DATABASE: &str = "database";
+
+/// One available `app.db` snapshot.
+#[derive(Serialize)]
+struct BackupEntry {
+    backup: PathBuf,
+}
+
+/// Result of restoring `app.db` from a snapshot.
+#[derive(Serialize)]
+struct RestoreSummary {
+    restored_from: PathBuf,
+    app_db: PathBuf,
+}
+
+/// Returns the clap configuration for the database subcommand.
+pub fn get_cli_setup() -> Command {
+    Command::new(DATABASE)
+        .about("manage the EPI2ME Desktop app.db")
+        .arg(arg!(--"list-backups" "list available app.db backups").action(ArgAction::SetTrue))
+        .arg(
+            arg!(--restore "restore app.db from a backup (filename or path)")
+                .action(ArgAction::Set)
+                .required(false)
+                .value_parser(value_parser!(String)),
+        )
+}
+
+/// Executes the database housekeeping flow.
+pub fn process_database_command(
+    args: &ArgMatches,
+    epi2me: &Epi2meOptions,
+) -> Result<(), Epi4youError> {
+    let list_backups = args
+        .get_one::<bool>("list-backups")
+        .copied()
+        .unwrap_or(false);
+    let restore = args.get_one::<String>("restore").cloned();
+
+    let setup = epi2me.require()?;
+
+    if list_backups {
+        let backups: Vec<BackupEntry> = app_db_backup::list_backups(&setup)?
+            .into_iter()
+            .map(|backup| BackupEntry { backup })
+            .collect();
+        output::emit(&backups);
+    } else if let Some(restore) = restore {
+        let restored_from = app_db_backup::restore_app_db(&setup, &restore)?;
+        log::info!("app.db restored - restart EPI2ME Desktop to see the change");
+        output::emit(&RestoreSummary {
+            restored_from,
+            app_db: setup.epi2db_path,
+        });
+    } else {
+        return Err(Epi4youError::AdditionalParameterRequired);
+    }
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::nextflow::nextflow_log_item::NxfLogItem;
+use epi4you::nextflow::nextflow_log_item::NxfLogItem;
 use polars::prelude::*;
 use std::env;
 
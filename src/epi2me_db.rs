@@ -1,11 +1,37 @@
-use crate::json;
+use crate::{app_db_backup::DEFAULT_BACKUP_RETENTION, json};
 use home::home_dir;
 use serde::{Deserialize, Serialize};
 use std::{
     fs::{self},
     path::PathBuf,
+    time::Duration,
 };
 
+/// Environment variable that names an explicit EPI2ME root directory.
+pub const EPI2ME_ROOT_ENV: &str = "EPI4YOU_EPI2ME_ROOT";
+
+/// Desktop `config.json` location on macOS, relative to `$HOME`.
+const MACOS_CONFIG_JSON: &str = "Library/Application Support/EPI2ME/config.json";
+/// Desktop `config.json` location on Linux, relative to `$HOME`.
+const LINUX_CONFIG_JSON: &str = ".config/EPI2ME/config.json";
+/// Data directory used when no `config.json` is present, relative to `$HOME`.
+const DEFAULT_EPI2ME_DIR: &str = "epi2melabs";
+
+/// Returns the data directory Desktop falls back to without a `config.json`.
+pub fn default_epi2me_root() -> Option<PathBuf> {
+    Some(home_dir()?.join(DEFAULT_EPI2ME_DIR))
+}
+
+/// Returns where Desktop expects its `config.json` on this platform.
+pub fn default_config_json() -> Option<PathBuf> {
+    let config = if cfg!(target_os = "macos") {
+        MACOS_CONFIG_JSON
+    } else {
+        LINUX_CONFIG_JSON
+    };
+    Some(home_dir()?.join(config))
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Epi2meSetup {
     pub epi2path: PathBuf,
@@ -14,18 +40,26 @@ pub struct Epi2meSetup {
     pub epi4you_path: PathBuf,
     pub instances_path: PathBuf,
     pub arch: String,
+    /// Number of `app.db` snapshots kept after each new one.
+    #[serde(default = "default_keep_backups")]
+    pub keep_backups: usize,
+    /// How long a write waits for Desktop to exit; `None` refuses at once.
+    #[serde(default)]
+    pub wait_for_desktop: Option<Duration>,
 }
 
+/// Discovers the installation Desktop uses from the running user's `$HOME`.
+///
+/// Use [`Epi2meSetup::from_root`] for any other installation.
 pub fn find_db() -> Option<Epi2meSetup> {
-    println!("locating the EPI2ME app.db");
+    log::debug!("locating the EPI2ME app.db");
 
     let home_dir = home_dir()?;
     let mut path: Option<PathBuf> = None;
 
-    let macos =
-        check_os_specific_db_path(&home_dir, "Library/Application Support/EPI2ME/config.json");
-    let linux = check_os_specific_db_path(&home_dir, ".config/EPI2ME/config.json");
-    let default = check_os_specific_db_path(&home_dir, "epi2melabs");
+    let macos = check_os_specific_db_path(&home_dir, MACOS_CONFIG_JSON);
+    let linux = check_os_specific_db_path(&home_dir, LINUX_CONFIG_JSON);
+    let default = check_os_specific_db_path(&home_dir, DEFAULT_EPI2ME_DIR);
 
     if macos.is_some() {
         path = macos;
@@ -35,7 +69,35 @@ pub fn find_db() -> Option<Epi2meSetup> {
         path = default;
     }
 
-    let path = path?;
+    setup_from_path(path?)
+}
+
+impl Epi2meSetup {
+    /// Builds the setup from an explicit EPI2ME root rather than `$HOME`.
+    ///
+    /// This is what makes it possible to prepare another user's account or a
+    /// mounted disk image where the running user's home directory is wrong.
+    pub fn from_root(root: &PathBuf) -> Option<Epi2meSetup> {
+        let path = if root.is_file() {
+            log::debug!("installation [{}]", root.display());
+            extract_epi2me_path(root)?
+        } else if root.is_dir() {
+            log::debug!("installation [{}]", root.display());
+            root.clone()
+        } else {
+            log::error!("EPI2ME root [{}] does not exist", root.display());
+            return None;
+        };
+        setup_from_path(path)
+    }
+}
+
+/// Builds the setup for the data directory `path`.
+///
+/// `path` is made canonical first, as the archive and instance paths derived
+/// from it are compared with absolute ones and recorded in `app.db`.
+fn setup_from_path(path: PathBuf) -> Option<Epi2meSetup> {
+    let path = fs::canonicalize(&path).ok()?;
     let db_path = get_appdb_path(&path)?;
     let mut instances_path = path.clone();
     instances_path.push("instances");
@@ -49,25 +111,31 @@ pub fn find_db() -> Option<Epi2meSetup> {
         epi4you_path: for_you_dir,
         instances_path,
         arch: String::from(std::env::consts::ARCH),
+        keep_backups: DEFAULT_BACKUP_RETENTION,
+        wait_for_desktop: None,
     })
 }
 
+fn default_keep_backups() -> usize {
+    DEFAULT_BACKUP_RETENTION
+}
+
 fn get_4you_path(app_db_path: &PathBuf) -> Option<PathBuf> {
     let mut x = app_db_path.clone();
     x.push("import_export_4you");
 
     if x.exists() {
-        println!("\t4you folder exists at [{}]", x.display());
+        log::debug!("4you folder exists at [{}]", x.display());
         return Some(x);
     }
 
     match fs::create_dir(&x) {
         Ok(_) => {
-            println!("\t4you folder created at [{}]", x.display());
+            log::info!("4you folder created at [{}]", x.display());
             Some(x)
         }
         Err(_) => {
-            eprintln!("\tErr - failed to create folder at [{}]", x.display());
+            log::error!("failed to create folder at [{}]", x.display());
             None
         }
     }
@@ -77,10 +145,10 @@ fn check_os_specific_db_path(home: &PathBuf, os_specific_path: &str) -> Option<P
     let mut pb = home.clone();
     pb.push(os_specific_path);
     if pb.exists() && pb.is_file() {
-        println!("\tinstallation [{}]", pb.display());
+        log::debug!("installation [{}]", pb.display());
         extract_epi2me_path(&pb)
     } else if pb.exists() && pb.is_dir() {
-        println!("\tinstallation [{}]", pb.display());
+        log::debug!("installation [{}]", pb.display());
         Some(pb)
     } else {
         None
@@ -88,7 +156,13 @@ fn check_os_specific_db_path(home: &PathBuf, os_specific_path: &str) -> Option<P
 }
 
 fn extract_epi2me_path(pb: &PathBuf) -> Option<PathBuf> {
-    let app_db_path = PathBuf::from(json::config_json(pb));
+    let app_db_path = match json::config_json(pb) {
+        Ok(working_dir) => PathBuf::from(working_dir),
+        Err(err) => {
+            log::warn!("{err}");
+            return None;
+        }
+    };
     if app_db_path.exists() && app_db_path.is_dir() {
         Some(app_db_path)
     } else {
@@ -100,7 +174,7 @@ fn get_appdb_path(app_db_path: &PathBuf) -> Option<PathBuf> {
     let mut x = app_db_path.clone();
     x.push("app.db");
     if x.exists() {
-        println!("\tapp.db exists at [{}]", x.display());
+        log::debug!("app.db exists at [{}]", x.display());
         Some(x)
     } else {
         None
@@ -111,7 +185,7 @@ fn get_epi2me_wfdir_path(app_db_path: &PathBuf) -> Option<PathBuf> {
     let mut x = app_db_path.clone();
     x.push("workflows");
     if x.exists() {
-        println!("\tworkflows folder exists at [{}]", x.display());
+        log::debug!("workflows folder exists at [{}]", x.display());
         Some(x)
     } else {
         None
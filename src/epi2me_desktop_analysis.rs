@@ -6,13 +6,16 @@
 //! bundled analysis can be exported from or imported into a Desktop-like
 //! environment.
 
-use std::{env, path::PathBuf};
+use std::path::{Path, PathBuf};
 
 use crate::{
     app_db::Epi2MeAnalysis,
     epi2me_workflow::clip_relative_path,
+    epi4you_errors::Epi4youError,
+    nextflow::nextflow_execution::ExecutionDetails,
     nextflow_log_parser::NextFlowLogs,
-    xmanifest::{sha256_digest, FileManifest},
+    progress::Progress,
+    xmanifest::{sha256_digest_with_progress, FileManifest},
 };
 use glob::glob;
 use serde::{Deserialize, Serialize};
@@ -47,17 +50,23 @@ pub struct Epi2meDesktopAnalysis {
     pub updatedAt: String,
     /// File inventory used when bundling this analysis.
     pub files: Vec<FileManifest>,
+    /// How the run was executed; only recorded for bundled CLI runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution: Option<Box<ExecutionDetails>>,
 }
 
 impl Epi2meDesktopAnalysis {
     /// Synthesizes a Desktop-style analysis record from a raw CLI Nextflow run.
+    ///
+    /// `status` is the Desktop status (`COMPLETED`, `ERROR` or `STOPPED`).
     pub fn init(
         ulid_str: &String,
         source: &PathBuf,
         nextflow_stdout: &String,
         timestamp: &String,
+        status: &str,
     ) -> Self {
-        println!("get_analysis_struct_from_cli");
+        log::debug!("get_analysis_struct_from_cli");
 
         let nlp = NextFlowLogs::init(nextflow_stdout);
         nlp.test();
@@ -66,7 +75,7 @@ impl Epi2meDesktopAnalysis {
             id: String::from(ulid_str),
             path: String::from(source.to_str().unwrap()),
             name: nlp.get_value("name"),
-            status: String::from("COMPLETED"),
+            status: String::from(status),
             workflowRepo: nlp.get_value("pname"),
             workflowUser: nlp.get_value("project"),
             workflowCommit: nlp.get_value("revision"),
@@ -74,6 +83,7 @@ impl Epi2meDesktopAnalysis {
             createdAt: String::from(timestamp),
             updatedAt: String::from(timestamp),
             files: Vec::<FileManifest>::new(),
+            execution: None,
         }
     }
 
@@ -94,34 +104,74 @@ impl Epi2meDesktopAnalysis {
     }
 
     /// Recursively inventories analysis files for bundling.
-    pub fn fish_files(&mut self, source: &PathBuf, local_prefix: &PathBuf) {
-        let globpat = &source.clone().into_os_string().into_string().unwrap();
+    pub fn fish_files(
+        &mut self,
+        source: &PathBuf,
+        local_prefix: &PathBuf,
+    ) -> Result<(), Epi4youError> {
+        self.fish(source, |path| clip_relative_path(path, local_prefix))
+    }
+
+    /// Inventories files that are archived from where they are.
+    ///
+    /// Files below `source` are recorded as if they lived below `archive_dir`,
+    /// which is relative to the archive root, so a large output folder can be
+    /// tarred without first being copied into the staging area.
+    pub fn fish_linked_files(
+        &mut self,
+        source: &PathBuf,
+        archive_dir: &Path,
+    ) -> Result<(), Epi4youError> {
+        self.fish(source, |path| {
+            let mut relative_path = archive_dir.join(path.strip_prefix(source).unwrap_or(path));
+            let _ = relative_path.pop();
+            Ok(relative_path)
+        })
+    }
+
+    fn fish(
+        &mut self,
+        source: &PathBuf,
+        relative_dir: impl Fn(&PathBuf) -> Result<PathBuf, Epi4youError>,
+    ) -> Result<(), Epi4youError> {
+        let globpat = source.to_string_lossy().to_string();
         let result = [&globpat, "/**/*.*"].join("");
 
-        println!("fishing for files at [{}]", result);
-
-        let _ = env::set_current_dir(globpat);
-
-        for entry in glob(&result).expect("Failed to read glob pattern") {
-            if let Ok(e) = entry {
-                let fname = &e.file_name().and_then(|s| s.to_str());
-                if e.is_file() && !fname.unwrap().contains("4u_manifest.json") {
-                    let fp = e.as_os_str().to_str().unwrap();
-                    let relative_path = clip_relative_path(&e, local_prefix);
-                    let checksum = sha256_digest(fp);
-                    let file_size = e.metadata().unwrap().len();
-
-                    self.files.push(FileManifest {
-                        filename: String::from(
-                            e.file_name().unwrap().to_os_string().to_str().unwrap(),
-                        ),
-                        relative_path: relative_path.to_string_lossy().to_string(),
-                        size: file_size,
-                        md5sum: checksum,
-                    });
-                }
+        log::info!("fishing for files at [{}]", result);
+
+        let entries = glob(&result)
+            .map_err(|err| Epi4youError::FailedToReadPath(source.clone(), err.into()))?;
+
+        // sizes are gathered up front so hashing can report progress in bytes
+        let mut candidates = Vec::new();
+        for e in entries.flatten() {
+            let fname = e
+                .file_name()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if e.is_file() && !fname.contains("4u_manifest.json") {
+                let file_size = e
+                    .metadata()
+                    .map_err(|err| Epi4youError::FailedToReadPath(e.clone(), err.into()))?
+                    .len();
+                candidates.push((e, fname, file_size));
             }
         }
+
+        let progress = Progress::new("hashing", candidates.iter().map(|c| c.2).sum());
+        for (e, fname, file_size) in candidates {
+            let relative_path = relative_dir(&e)?;
+            let checksum = sha256_digest_with_progress(&e, &progress)?;
+
+            self.files.push(FileManifest {
+                filename: fname,
+                relative_path: relative_path.to_string_lossy().to_string(),
+                size: file_size,
+                md5sum: checksum,
+            });
+        }
+        progress.finish();
+        Ok(())
     }
 
     /// Returns the current file manifest vector.
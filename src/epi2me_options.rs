@@ -0,0 +1,45 @@
+//! The EPI2ME installation selected on the command line.
+//!
+//! `--epi2me-root`, `--keep-backups` and `--wait`, or their configuration and
+//! environment equivalents, apply to every subcommand, while the library takes
+//! the installation as an [`Epi2meSetup`] argument. They are gathered once in
+//! `main` and each subcommand asks for the setup they describe.
+
+use std::{path::PathBuf, time::Duration};
+
+use epi4you::{epi2me_db, Epi2meSetup, Epi4youError};
+
+/// Global options that shape the EPI2ME installation written to.
+pub struct Epi2meOptions {
+    /// Explicit data directory or Desktop `config.json`.
+    pub root: Option<PathBuf>,
+    /// Number of `app.db` backups to retain.
+    pub keep_backups: Option<usize>,
+    /// How long to wait for Desktop to exit before writing.
+    pub wait: Option<Duration>,
+}
+
+impl Epi2meOptions {
+    /// Returns the selected installation: the explicit root if there is one,
+    /// otherwise the one Desktop uses.
+    pub fn find(&self) -> Option<Epi2meSetup> {
+        let mut setup = match &self.root {
+            Some(root) => {
+                log::info!("using explicit EPI2ME root [{}]", root.display());
+                Epi2meSetup::from_root(root)
+            }
+            None => epi2me_db::find_db(),
+        }?;
+        if let Some(keep) = self.keep_backups {
+            setup.keep_backups = keep;
+        }
+        setup.wait_for_desktop = self.wait;
+        Some(setup)
+    }
+
+    /// Returns the selected installation, failing if there is none.
+    pub fn require(&self) -> Result<Epi2meSetup, Epi4youError> {
+        self.find()
+            .ok_or(Epi4youError::UnableToLocateEpi2meDatabase)
+    }
+}
@@ -1,39 +1,74 @@
-use std::{fs::File, path::PathBuf};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
 
-use tar::Builder;
+use tar::{Builder, Header};
 
-use crate::{epi2me_db, xmanifest::FileManifest};
+use crate::{epi4you_errors::Epi4youError, progress::Progress, xmanifest::FileManifest};
 
+/// Writes the listed files and the manifest into a new tarball.
+///
+/// `files` and `manifest` are relative to `local_prefix`, usually the EPI2ME
+/// root; that relative path is also the name each entry is given in the archive. Files
+/// named below the first folder of a `linked` pair are instead read from
+/// below the second, so folders can be archived without being staged.
 pub fn tar(
-    wf_path: Option<&PathBuf>,
+    local_prefix: &Path,
     tarfile: PathBuf,
     files: &Vec<FileManifest>,
     manifest: &PathBuf,
-) {
-    let tarfile = File::create(tarfile).unwrap();
-    let mut a = Builder::new(tarfile);
-
-    let mut local_prefix = PathBuf::from("/");
-    if wf_path.is_some() {
-        local_prefix = wf_path.unwrap().to_owned();
-    } else if let Some(epi2db) = epi2me_db::find_db() {
-        local_prefix = epi2db.epi2path;
-    }
+    linked: &[(PathBuf, PathBuf)],
+) -> Result<(), Epi4youError> {
+    let tarball = File::create(&tarfile)
+        .map_err(|err| Epi4youError::FailedToWritePath(tarfile.clone(), err.into()))?;
+    let mut a = Builder::new(tarball);
 
+    let progress = Progress::new("archiving", files.iter().map(|file| file.size).sum());
     for file in files {
-        let mut file_to_tar = local_prefix.join(&file.relative_path);
-        file_to_tar.push(&file.filename);
-
-        println!(
-            "adding file [{}] to tarball",
-            file_to_tar.as_os_str().to_str().unwrap()
-        );
+        let name = Path::new(&file.relative_path).join(&file.filename);
+        let file_to_tar = linked_source(&name, linked).unwrap_or_else(|| local_prefix.join(&name));
 
-        let _ = a.append_path(file_to_tar);
+        log::debug!("adding file [{}] to tarball", file_to_tar.display());
+        append_file(&mut a, &file_to_tar, &name, &progress)?;
     }
+    progress.finish();
+
+    log::info!("writing manifest {:?}", manifest);
+    let manifest_path = local_prefix.join(manifest);
+    a.append_path_with_name(&manifest_path, manifest)
+        .map_err(|err| Epi4youError::FailedToReadPath(manifest_path.clone(), err.into()))?;
+    a.finish()
+        .map_err(|err| Epi4youError::FailedToWritePath(tarfile, err.into()))
+}
+
+/// Returns where a file recorded as `name` is read from if it lies below the
+/// first folder of one of the `linked` pairs.
+pub fn linked_source(name: &Path, linked: &[(PathBuf, PathBuf)]) -> Option<PathBuf> {
+    linked.iter().find_map(|(archive_dir, source)| {
+        name.strip_prefix(archive_dir)
+            .ok()
+            .map(|path| source.join(path))
+    })
+}
+
+/// Appends one file under `name`, counting the bytes read as progress.
+fn append_file(
+    a: &mut Builder<File>,
+    path: &Path,
+    name: &Path,
+    progress: &Progress,
+) -> Result<(), Epi4youError> {
+    let file = File::open(path)
+        .map_err(|err| Epi4youError::FailedToReadPath(path.to_path_buf(), err.into()))?;
+    let metadata = file
+        .metadata()
+        .map_err(|err| Epi4youError::FailedToReadPath(path.to_path_buf(), err.into()))?;
 
-    println!("writing manifest {:?}", manifest);
-    let _ = a.append_path(manifest);
+    let mut header = Header::new_gnu();
+    header.set_metadata(&metadata);
+    a.append_data(&mut header, name, progress.wrap_read(file))
+        .map_err(|err| Epi4youError::FailedToReadPath(path.to_path_buf(), err.into()))
 }
 
 /*
@@ -4,7 +4,7 @@
 //! `epi4you` can package those workflow assets directly so they can travel with
 //! an analysis archive or be reinstalled elsewhere.
 
-use crate::xmanifest::FileManifest;
+use crate::{epi4you_errors::Epi4youError, xmanifest::FileManifest};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -23,13 +23,17 @@ pub struct Epi2meWorkflow {
 }
 
 /// Returns the relative parent directory that should contain a given file.
-pub fn clip_relative_path(e: &PathBuf, local_prefix: &PathBuf) -> PathBuf {
-    let mut relative_path = get_relative_path(e, local_prefix);
+pub fn clip_relative_path(e: &PathBuf, local_prefix: &PathBuf) -> Result<PathBuf, Epi4youError> {
+    let mut relative_path = get_relative_path(e, local_prefix)?;
     let _ = relative_path.pop();
-    relative_path
+    Ok(relative_path)
 }
 
 /// Returns the path of `e` relative to the supplied EPI2ME installation root.
-pub fn get_relative_path(e: &PathBuf, local_prefix: &PathBuf) -> PathBuf {
-    PathBuf::from(e.strip_prefix(local_prefix).unwrap())
+///
+/// Fails if `e` does not lie below `local_prefix`.
+pub fn get_relative_path(e: &PathBuf, local_prefix: &PathBuf) -> Result<PathBuf, Epi4youError> {
+    e.strip_prefix(local_prefix)
+        .map(PathBuf::from)
+        .map_err(|err| Epi4youError::FailedToReadPath(e.clone(), err.into()))
 }
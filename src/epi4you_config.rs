@@ -0,0 +1,299 @@
+//! Defaults for command-line options read from `epi4you.toml`.
+//!
+//! Two files are consulted: one in the user's config directory and one in the
+//! current directory, so that a project can pin its own Nextflow binary or
+//! archive destination. Values from the project file win over the user file,
+//! and anything given on the command line or through an environment variable
+//! wins over both.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use clap::{parser::ValueSource, ArgMatches};
+use home::home_dir;
+use serde::{Deserialize, Serialize};
+
+use epi4you::{
+    app_db_backup, epi2me_db,
+    epi4you_errors::{Epi4youError, ErrorSource},
+};
+
+use crate::output::{OutputFormat, OUTPUT_FORMATS};
+
+/// File name of both the user and the project-local configuration.
+pub const CONFIG_FILE: &str = "epi4you.toml";
+/// Folder below the user config directory holding [`CONFIG_FILE`].
+const CONFIG_DIR: &str = "epi4you";
+
+/// Keys accepted in `epi4you.toml`; every key is optional.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    nxf_bin: Option<String>,
+    nxf_work: Option<String>,
+    twome_dir: Option<PathBuf>,
+    force: Option<bool>,
+    epi2me_root: Option<PathBuf>,
+    keep_backups: Option<usize>,
+    output: Option<String>,
+}
+
+/// A configured value together with where it was read from.
+#[derive(Clone, Debug)]
+struct Setting<T> {
+    value: T,
+    source: String,
+}
+
+/// Effective configuration after merging the user and project files.
+#[derive(Default)]
+pub struct Config {
+    /// Path to the `nextflow` executable.
+    nxf_bin: Option<Setting<String>>,
+    /// Folder holding the Nextflow runs to package.
+    nxf_work: Option<Setting<String>>,
+    /// Folder `.2me` archives are written to when `--twome` is omitted.
+    twome_dir: Option<Setting<PathBuf>>,
+    /// Overwrite existing archives and content without `--force`.
+    force: Option<Setting<bool>>,
+    /// EPI2ME data directory, as for `--epi2me-root`.
+    epi2me_root: Option<Setting<PathBuf>>,
+    /// Number of `app.db` backups to retain.
+    keep_backups: Option<Setting<usize>>,
+    /// Output format, as for `--output`.
+    output: Option<Setting<String>>,
+}
+
+/// One row of `epi4you config show`.
+#[derive(Serialize)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: String,
+    pub source: String,
+}
+
+impl Config {
+    /// Reads the user and project-local configuration files.
+    ///
+    /// Missing files are skipped; a file that exists but cannot be parsed is
+    /// an error so that a typo is not silently ignored.
+    pub fn load() -> Result<Self, Epi4youError> {
+        let mut config = Config::default();
+        for path in config_files() {
+            if path.is_file() {
+                log::debug!("reading configuration from [{}]", path.display());
+                config.merge(read_config_file(&path)?, &path);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Overlays the values of `file` on this configuration.
+    fn merge(&mut self, file: ConfigFile, path: &Path) {
+        let source = path.display().to_string();
+        overlay(&mut self.nxf_bin, file.nxf_bin, &source);
+        overlay(&mut self.nxf_work, file.nxf_work, &source);
+        overlay(&mut self.twome_dir, file.twome_dir, &source);
+        overlay(&mut self.force, file.force, &source);
+        overlay(&mut self.epi2me_root, file.epi2me_root, &source);
+        overlay(&mut self.keep_backups, file.keep_backups, &source);
+        overlay(&mut self.output, file.output, &source);
+    }
+
+    /// Returns the configured `nextflow` binary, if any.
+    pub fn nxf_bin(&self) -> Option<String> {
+        self.nxf_bin.as_ref().map(|setting| setting.value.clone())
+    }
+
+    /// Returns the configured Nextflow work folder, if any.
+    pub fn nxf_work(&self) -> Option<String> {
+        self.nxf_work.as_ref().map(|setting| setting.value.clone())
+    }
+
+    /// Returns the configured archive destination folder, if any.
+    pub fn twome_dir(&self) -> Option<PathBuf> {
+        self.twome_dir.as_ref().map(|setting| setting.value.clone())
+    }
+
+    /// Returns whether overwriting is enabled by default.
+    pub fn force(&self) -> bool {
+        self.force.as_ref().is_some_and(|setting| setting.value)
+    }
+
+    /// Returns the configured EPI2ME root, if any.
+    pub fn epi2me_root(&self) -> Option<PathBuf> {
+        self.epi2me_root
+            .as_ref()
+            .map(|setting| setting.value.clone())
+    }
+
+    /// Returns the configured backup retention, if any.
+    pub fn keep_backups(&self) -> Option<usize> {
+        self.keep_backups.as_ref().map(|setting| setting.value)
+    }
+
+    /// Returns the configured output format, if any.
+    pub fn output(&self) -> Option<String> {
+        self.output.as_ref().map(|setting| setting.value.clone())
+    }
+
+    /// Lists every key with its effective value and origin.
+    ///
+    /// Environment variables that override a key are taken into account, and
+    /// keys nobody set are reported with their built-in default.
+    pub fn entries(&self) -> Vec<ConfigEntry> {
+        let mut entries = Vec::new();
+        let mut push = |key: &str, value: Option<(String, String)>, default: &str| {
+            let (value, source) =
+                value.unwrap_or_else(|| (String::from(default), String::from("default")));
+            entries.push(ConfigEntry {
+                key: String::from(key),
+                value,
+                source,
+            });
+        };
+
+        push("nxf_bin", shown(&self.nxf_bin), "");
+        push("nxf_work", shown(&self.nxf_work), "");
+        push("twome_dir", shown_path(&self.twome_dir), "");
+        push("force", shown(&self.force), "false");
+        push(
+            "epi2me_root",
+            from_env(epi2me_db::EPI2ME_ROOT_ENV).or_else(|| shown_path(&self.epi2me_root)),
+            "",
+        );
+        push(
+            "keep_backups",
+            from_env(app_db_backup::BACKUP_RETENTION_ENV).or_else(|| shown(&self.keep_backups)),
+            &app_db_backup::DEFAULT_BACKUP_RETENTION.to_string(),
+        );
+        push("output", shown(&self.output), "table");
+        entries
+    }
+}
+
+/// Returns true if `id` was given on the command line or via the environment.
+pub fn is_explicit(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine) | Some(ValueSource::EnvVariable)
+    )
+}
+
+/// Returns the configuration files in increasing order of precedence.
+pub fn config_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let user_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".config")));
+    if let Some(user_dir) = user_dir {
+        files.push(user_dir.join(CONFIG_DIR).join(CONFIG_FILE));
+    }
+    if let Ok(cwd) = env::current_dir() {
+        files.push(cwd.join(CONFIG_FILE));
+    }
+    files
+}
+
+fn overlay<T>(slot: &mut Option<Setting<T>>, value: Option<T>, source: &str) {
+    if let Some(value) = value {
+        *slot = Some(Setting {
+            value,
+            source: String::from(source),
+        });
+    }
+}
+
+fn read_config_file(path: &Path) -> Result<ConfigFile, Epi4youError> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| Epi4youError::FailedToReadPath(path.to_path_buf(), err.into()))?;
+    let file: ConfigFile = toml::from_str(&content)
+        .map_err(|err| Epi4youError::InvalidConfigurationFile(path.to_path_buf(), err.into()))?;
+
+    if let Some(format) = &file.output {
+        if OutputFormat::from_name(format).is_none() {
+            return Err(Epi4youError::InvalidConfigurationFile(
+                path.to_path_buf(),
+                ErrorSource::msg(format!(
+                    "output must be one of {}",
+                    OUTPUT_FORMATS.join(", ")
+                )),
+            ));
+        }
+    }
+    Ok(file)
+}
+
+fn shown<T: ToString>(setting: &Option<Setting<T>>) -> Option<(String, String)> {
+    setting
+        .as_ref()
+        .map(|setting| (setting.value.to_string(), setting.source.clone()))
+}
+
+fn shown_path(setting: &Option<Setting<PathBuf>>) -> Option<(String, String)> {
+    setting
+        .as_ref()
+        .map(|setting| (setting.value.display().to_string(), setting.source.clone()))
+}
+
+fn from_env(name: &str) -> Option<(String, String)> {
+    let value = env::var(name).ok().filter(|value| !value.is_empty())?;
+    Some((value, format!("environment ({name})")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{Config, ConfigFile};
+
+    #[test]
+    fn project_file_overrides_user_file() {
+        let user: ConfigFile =
+            toml::from_str("nxf_bin = \"/usr/bin/nextflow\"\nforce = true\n").unwrap();
+        let project: ConfigFile = toml::from_str("nxf_bin = \"./nextflow\"\n").unwrap();
+
+        let mut config = Config::default();
+        config.merge(user, Path::new("/home/user/.config/epi4you/epi4you.toml"));
+        config.merge(project, Path::new("/project/epi4you.toml"));
+
+        assert_eq!(config.nxf_bin(), Some(String::from("./nextflow")));
+        assert!(config.force());
+        let entries = config.entries();
+        let nxf_bin = entries.iter().find(|entry| entry.key == "nxf_bin").unwrap();
+        assert_eq!(nxf_bin.source, "/project/epi4you.toml");
+        let force = entries.iter().find(|entry| entry.key == "force").unwrap();
+        assert_eq!(force.source, "/home/user/.config/epi4you/epi4you.toml");
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<ConfigFile>("nxf_binary = \"nextflow\"\n").is_err());
+    }
+
+    #[test]
+    fn out_of_scope_keys_are_rejected() {
+        for key in [
+            "compression = \"zstd\"",
+            "signing_key = \"/keys/epi4you.pem\"",
+        ] {
+            assert!(toml::from_str::<ConfigFile>(key).is_err());
+        }
+    }
+
+    #[test]
+    fn unset_keys_report_defaults() {
+        let entries = Config::default().entries();
+        let output = entries.iter().find(|entry| entry.key == "output").unwrap();
+        assert_eq!(output.value, "table");
+        assert_eq!(output.source, "default");
+        assert_eq!(
+            Config::default().twome_dir(),
+            None::<PathBuf>,
+            "no destination without configuration"
+        );
+    }
+}
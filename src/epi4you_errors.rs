@@ -1,27 +1,279 @@
-use std::path::PathBuf;
+use std::{error::Error, fmt, path::PathBuf};
 
-use serde::Serialize;
+use serde::{Serialize, Serializer};
+
+/// Underlying error that caused an [`Epi4youError`].
+///
+/// Kept so that `source()` chaining works for callers, and serialized as its
+/// message so the enum can still be emitted as JSON.
+#[derive(Debug)]
+pub struct ErrorSource(Box<dyn Error + Send + Sync>);
+
+impl ErrorSource {
+    /// Builds a source from a plain message where no error value exists.
+    pub fn msg(message: impl Into<String>) -> Self {
+        ErrorSource(message.into().into())
+    }
+}
+
+impl<E: Error + Send + Sync + 'static> From<E> for ErrorSource {
+    fn from(err: E) -> Self {
+        ErrorSource(Box::new(err))
+    }
+}
+
+impl fmt::Display for ErrorSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for ErrorSource {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+/// Broad classes of failure, each with its own process exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ErrorCategory {
+    /// The command line was incomplete or contradictory.
+    Usage,
+    /// A file, run or installation the command needs could not be found.
+    MissingInput,
+    /// An archive or file failed verification or could not be parsed.
+    Integrity,
+    /// The EPI2ME `app.db` could not be read or written.
+    Database,
+    /// An external program such as `nextflow` failed or was unavailable.
+    ExternalTool,
+    /// Reading or writing the local filesystem failed.
+    Filesystem,
+}
+
+impl ErrorCategory {
+    /// Process exit code that scripts can branch on.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorCategory::Usage => 2,
+            ErrorCategory::MissingInput => 3,
+            ErrorCategory::Integrity => 4,
+            ErrorCategory::Database => 5,
+            ErrorCategory::ExternalTool => 6,
+            ErrorCategory::Filesystem => 7,
+        }
+    }
+}
 
 #[derive(Debug, Serialize)]
 pub enum Epi4youError {
     AdditionalParameterRequired,
     CannotVerifyManifestAuthenticity,
+    DatabaseIsLocked(PathBuf),
+    DestinationInsideSource(PathBuf),
+    Epi2meDesktopIsRunning,
+    Epi2meDesktopStillRunning(u64),
     Epi4youMissingRequired2MEartefact,
-    ErrorInUnpackingTarElement,
-    FailedToCreateFolder(PathBuf),
-    FailedToReadPath(PathBuf),
-    FailedToParseFileContent,
-    FailedToRunCommand(String),
-    FailedToWritePath(PathBuf),
+    ErrorInUnpackingTarElement(ErrorSource),
+    ExecutionTraceNotFound(String),
+    FailedToCreateFolder(PathBuf, ErrorSource),
+    FailedToOpenDatabase(PathBuf, ErrorSource),
+    FailedToParseFileContent(ErrorSource),
+    FailedToQueryDatabase(ErrorSource),
+    FailedToReadPath(PathBuf, ErrorSource),
+    FailedToRunCommand(String, ErrorSource),
+    FailedToWritePath(PathBuf, ErrorSource),
     FileAlreadyExistsUnforcedExecution(PathBuf),
     FileFoundWhenFolderExpected(PathBuf),
     FileSelectionFailedFileNotFound,
     FileSelectionIsAmbiguous,
     FolderFoundWhenFileExpected(PathBuf),
+    InvalidConfigurationFile(PathBuf, ErrorSource),
     MalformedCLISetup,
     NextflowAnalysisFolderNotFound,
+    NextflowRunNotSuccessful(String, String),
     RequiredPathMissing(PathBuf),
     SpecifiedNextflowRunNotFound(String),
+    UnableToLocateEpi2meDatabase,
     UnableToLocateNextflowBinary,
     UnableToResolveManifestObject,
+    UnsupportedDatabaseSchema(String),
+}
+
+impl Epi4youError {
+    /// Returns the category used to pick the process exit code.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Epi4youError::AdditionalParameterRequired
+            | Epi4youError::DestinationInsideSource(_)
+            | Epi4youError::FileAlreadyExistsUnforcedExecution(_)
+            | Epi4youError::FileFoundWhenFolderExpected(_)
+            | Epi4youError::FileSelectionIsAmbiguous
+            | Epi4youError::FolderFoundWhenFileExpected(_)
+            | Epi4youError::InvalidConfigurationFile(..)
+            | Epi4youError::MalformedCLISetup
+            | Epi4youError::NextflowRunNotSuccessful(..) => ErrorCategory::Usage,
+            Epi4youError::Epi4youMissingRequired2MEartefact
+            | Epi4youError::ExecutionTraceNotFound(_)
+            | Epi4youError::FileSelectionFailedFileNotFound
+            | Epi4youError::NextflowAnalysisFolderNotFound
+            | Epi4youError::RequiredPathMissing(_)
+            | Epi4youError::SpecifiedNextflowRunNotFound(_)
+            | Epi4youError::UnableToLocateEpi2meDatabase
+            | Epi4youError::UnableToResolveManifestObject => ErrorCategory::MissingInput,
+            Epi4youError::CannotVerifyManifestAuthenticity
+            | Epi4youError::ErrorInUnpackingTarElement(_)
+            | Epi4youError::FailedToParseFileContent(_) => ErrorCategory::Integrity,
+            Epi4youError::DatabaseIsLocked(_)
+            | Epi4youError::Epi2meDesktopIsRunning
+            | Epi4youError::Epi2meDesktopStillRunning(_)
+            | Epi4youError::FailedToOpenDatabase(..)
+            | Epi4youError::FailedToQueryDatabase(_)
+            | Epi4youError::UnsupportedDatabaseSchema(_) => ErrorCategory::Database,
+            Epi4youError::FailedToRunCommand(..) | Epi4youError::UnableToLocateNextflowBinary => {
+                ErrorCategory::ExternalTool
+            }
+            Epi4youError::FailedToCreateFolder(..)
+            | Epi4youError::FailedToReadPath(..)
+            | Epi4youError::FailedToWritePath(..) => ErrorCategory::Filesystem,
+        }
+    }
+
+    /// Returns the process exit code for this error.
+    pub fn exit_code(&self) -> i32 {
+        self.category().exit_code()
+    }
+}
+
+impl fmt::Display for Epi4youError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Epi4youError::AdditionalParameterRequired => write!(
+                f,
+                "a required option is missing - see `--help` for this subcommand"
+            ),
+            Epi4youError::CannotVerifyManifestAuthenticity => write!(
+                f,
+                "the archive manifest failed verification - the archive may be corrupt or modified"
+            ),
+            Epi4youError::DatabaseIsLocked(path) => write!(
+                f,
+                "[{}] is locked by another process - close EPI2ME Desktop and retry",
+                path.display()
+            ),
+            Epi4youError::DestinationInsideSource(path) => write!(
+                f,
+                "destination [{}] lies inside the folder being bundled - choose another location",
+                path.display()
+            ),
+            Epi4youError::Epi2meDesktopIsRunning => write!(
+                f,
+                "EPI2ME Desktop is running - close it first or re-run with `--wait`"
+            ),
+            Epi4youError::Epi2meDesktopStillRunning(seconds) => write!(
+                f,
+                "EPI2ME Desktop was still running after waiting {seconds}s - close it and try again"
+            ),
+            Epi4youError::Epi4youMissingRequired2MEartefact => {
+                write!(f, "no .2me archive given - pass one with `--twome <file>`")
+            }
+            Epi4youError::ErrorInUnpackingTarElement(_) => {
+                write!(f, "failed to unpack the archive - it may be truncated or corrupt")
+            }
+            Epi4youError::ExecutionTraceNotFound(run) => write!(
+                f,
+                "no execution trace found for run [{run}] - was it launched with `-with-trace`?"
+            ),
+            Epi4youError::FailedToCreateFolder(path, _) => {
+                write!(f, "failed to create folder [{}]", path.display())
+            }
+            Epi4youError::FailedToOpenDatabase(path, _) => {
+                write!(f, "failed to open database [{}]", path.display())
+            }
+            Epi4youError::FailedToParseFileContent(_) => {
+                write!(f, "failed to parse file content")
+            }
+            Epi4youError::FailedToQueryDatabase(_) => write!(f, "database operation failed"),
+            Epi4youError::FailedToReadPath(path, _) => {
+                write!(f, "failed to read [{}]", path.display())
+            }
+            Epi4youError::FailedToRunCommand(command, _) => {
+                write!(f, "failed to run `{command}`")
+            }
+            Epi4youError::FailedToWritePath(path, _) => {
+                write!(f, "failed to write [{}]", path.display())
+            }
+            Epi4youError::FileAlreadyExistsUnforcedExecution(path) => write!(
+                f,
+                "[{}] already exists - re-run with `--force` to overwrite",
+                path.display()
+            ),
+            Epi4youError::FileFoundWhenFolderExpected(path) => {
+                write!(f, "[{}] is a file but a folder was expected", path.display())
+            }
+            Epi4youError::FileSelectionFailedFileNotFound => write!(
+                f,
+                "no matching Nextflow log was found - has the run folder been cleaned up?"
+            ),
+            Epi4youError::FileSelectionIsAmbiguous => {
+                write!(f, "more than one Nextflow log matches this run")
+            }
+            Epi4youError::FolderFoundWhenFileExpected(path) => {
+                write!(f, "[{}] is a folder but a file was expected", path.display())
+            }
+            Epi4youError::InvalidConfigurationFile(path, _) => write!(
+                f,
+                "configuration file [{}] is invalid - fix or remove it",
+                path.display()
+            ),
+            Epi4youError::MalformedCLISetup => write!(f, "unrecognised subcommand"),
+            Epi4youError::NextflowAnalysisFolderNotFound => write!(
+                f,
+                "the run's output folder could not be found - name it with `--out-dir`"
+            ),
+            Epi4youError::NextflowRunNotSuccessful(run, status) => write!(
+                f,
+                "run [{run}] did not complete (status {status}) - pass `--include-failed` to bundle it anyway"
+            ),
+            Epi4youError::RequiredPathMissing(path) => {
+                write!(f, "[{}] does not exist", path.display())
+            }
+            Epi4youError::SpecifiedNextflowRunNotFound(run) => write!(
+                f,
+                "no Nextflow run named [{run}] - use `--list` to see available runs"
+            ),
+            Epi4youError::UnableToLocateEpi2meDatabase => write!(
+                f,
+                "no EPI2ME installation found - use `--epi2me-root` or run `epi4you init`"
+            ),
+            Epi4youError::UnableToLocateNextflowBinary => write!(
+                f,
+                "nextflow could not be found - install it or pass `--nxf_bin <path>`"
+            ),
+            Epi4youError::UnableToResolveManifestObject => {
+                write!(f, "the archive does not contain an epi4you manifest")
+            }
+            Epi4youError::UnsupportedDatabaseSchema(column) => write!(
+                f,
+                "app.db has an unsupported layout (cannot satisfy `{column}`) - this Desktop version is not supported"
+            ),
+        }
+    }
+}
+
+impl Error for Epi4youError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Epi4youError::ErrorInUnpackingTarElement(source)
+            | Epi4youError::FailedToCreateFolder(_, source)
+            | Epi4youError::FailedToOpenDatabase(_, source)
+            | Epi4youError::FailedToParseFileContent(source)
+            | Epi4youError::FailedToQueryDatabase(source)
+            | Epi4youError::FailedToReadPath(_, source)
+            | Epi4youError::FailedToRunCommand(_, source)
+            | Epi4youError::FailedToWritePath(_, source)
+            | Epi4youError::InvalidConfigurationFile(_, source) => Some(source.0.as_ref()),
+            _ => None,
+        }
+    }
 }
@@ -7,12 +7,22 @@
 use std::path::PathBuf;
 
 use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
+use serde::Serialize;
 
-use crate::{epi4you_errors::Epi4youError, tempdir::TempDir, xmanifest};
+use epi4you::{Epi2MeAnalysis, Epi4youError};
+
+use crate::{epi2me_options::Epi2meOptions, epi4you_config::Config, output};
 
 /// CLI subcommand name used for `.2me` import.
 pub const IMPORT2ME: &str = "import";
 
+/// Result of importing one archive.
+#[derive(Serialize)]
+struct ImportSummary {
+    archive: PathBuf,
+    analyses: Vec<Epi2MeAnalysis>,
+}
+
 /// Returns the clap configuration for the archive import subcommand.
 pub fn get_cli_setup() -> Command {
     let my_command = Command::new(IMPORT2ME)
@@ -34,25 +44,21 @@ pub fn get_cli_setup() -> Command {
 /// modified.
 pub async fn process_2me_import_command(
     args: &ArgMatches,
-    tempdir: &TempDir,
+    config: &Config,
+    epi2me: &Epi2meOptions,
 ) -> Result<(), Epi4youError> {
     let twome = args.get_one::<String>("twome").cloned();
-    let force = args.get_one::<bool>("force").copied().unwrap_or(false);
-
-    // ximporter::import_coordinator(&tempdir.path, twome, force).await;
+    let force = args.get_flag("force") || config.force();
 
     let twome = twome.ok_or(Epi4youError::Epi4youMissingRequired2MEartefact)?;
 
     let path = PathBuf::from(twome);
-    if !path.exists() {
-        return Err(Epi4youError::RequiredPathMissing(path));
-    } else if path.is_dir() {
-        return Err(Epi4youError::FolderFoundWhenFileExpected(path));
-    }
-
-    let mut manifest = xmanifest::Epi2MeManifest::from_tarball(path.clone())?;
-    let _payload = manifest.unpack_container_content(&tempdir.path, &path, &force)?;
-    manifest.process_container_content(&tempdir.path)?;
+    let epi2me = epi2me.require()?;
+    let analyses = epi4you::import_archive(&epi2me, path.clone(), force)?;
 
+    output::emit(&ImportSummary {
+        archive: path,
+        analyses,
+    });
     Ok(())
 }
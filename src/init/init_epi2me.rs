@@ -0,0 +1,89 @@
+//! CLI entry point for bootstrapping an empty EPI2ME Desktop data directory.
+//!
+//! Desktop only creates its `app.db` the first time the GUI is launched, so on
+//! a freshly imaged machine there is nowhere for an import to land. This flow
+//! lays down the same skeleton Desktop would, which lets analyses be staged
+//! before anyone has opened the application.
+
+use std::path::{Path, PathBuf};
+
+use clap::{arg, value_parser, ArgAction, ArgMatches, Command};
+use serde::Serialize;
+
+use epi4you::{epi2me_db, Epi4youError};
+
+use crate::{epi2me_options::Epi2meOptions, output};
+
+/// CLI subcommand name for initialising an EPI2ME data directory.
+pub const INIT: &str = "init";
+
+/// Result of initialising a data directory.
+#[derive(Serialize)]
+struct InitSummary {
+    epi2me_root: PathBuf,
+    app_db: PathBuf,
+    config: PathBuf,
+}
+
+/// Returns the clap configuration for the initialisation subcommand.
+pub fn get_cli_setup() -> Command {
+    let my_command = Command::new(INIT)
+        .about("initialise an empty EPI2ME Desktop data directory")
+        .arg(
+            arg!(--config "Desktop config.json to point at the data directory")
+                .action(ArgAction::Set)
+                .required(false)
+                .value_parser(value_parser!(String)),
+        )
+        .arg(
+            arg!(--force "repoint an existing config.json at this data directory")
+                .action(ArgAction::SetTrue),
+        );
+    my_command
+}
+
+/// Executes the initialisation flow.
+///
+/// The data directory is taken from `--epi2me-root` when given and otherwise
+/// defaults to the `epi2melabs` folder Desktop itself falls back to. The
+/// operator's own `config.json` is only the default for that default folder;
+/// any other root, such as one on a disk image for another machine, needs
+/// `--config`. Running the command again is harmless: existing folders and
+/// tables are kept.
+pub fn process_init_command(args: &ArgMatches, epi2me: &Epi2meOptions) -> Result<(), Epi4youError> {
+    let force = args.get_one::<bool>("force").copied().unwrap_or(false);
+    let default_root = epi2me_db::default_epi2me_root();
+    let root = epi2me
+        .root
+        .clone()
+        .or_else(|| default_root.clone())
+        .ok_or(Epi4youError::AdditionalParameterRequired)?;
+    let config = match args.get_one::<String>("config") {
+        Some(config) => PathBuf::from(config),
+        None if is_same_folder(&root, default_root.as_deref()) => {
+            epi2me_db::default_config_json().ok_or(Epi4youError::AdditionalParameterRequired)?
+        }
+        None => {
+            log::error!(
+                "[{}] is not the default data directory - name the config.json to point at it with `--config`",
+                root.display()
+            );
+            return Err(Epi4youError::AdditionalParameterRequired);
+        }
+    };
+
+    let setup = epi4you::init_epi2me(&root, &config, force)?;
+
+    output::emit(&InitSummary {
+        epi2me_root: setup.epi2path,
+        app_db: setup.epi2db_path,
+        config,
+    });
+    Ok(())
+}
+
+/// Returns true if `root` names the same folder as `default`.
+fn is_same_folder(root: &Path, default: Option<&Path>) -> bool {
+    let absolute = |path: &Path| std::path::absolute(path).ok();
+    default.is_some_and(|default| absolute(root) == absolute(default))
+}
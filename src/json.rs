@@ -1,5 +1,11 @@
 use serde::Deserialize;
-use std::{fs::File, path::PathBuf};
+use serde_json::{Map, Value};
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use crate::epi4you_errors::Epi4youError;
 
 #[derive(Deserialize)]
 #[allow(non_snake_case)]
@@ -7,11 +13,74 @@ struct SomeDataType {
     workingDirectory: String,
 }
 
-pub fn config_json(path_buf: &PathBuf) -> String {
-    let json_file = File::open(path_buf).expect("file not found");
-    let epi2me_setup: SomeDataType =
-        serde_json::from_reader(json_file).expect("error while reading json");
+pub fn config_json(path_buf: &PathBuf) -> Result<String, Epi4youError> {
+    let json_file = File::open(path_buf)
+        .map_err(|err| Epi4youError::FailedToReadPath(path_buf.clone(), err.into()))?;
+    let epi2me_setup: SomeDataType = serde_json::from_reader(json_file)
+        .map_err(|err| Epi4youError::FailedToParseFileContent(err.into()))?;
+
+    log::debug!("json parsed [workDir={}]", epi2me_setup.workingDirectory);
+    Ok(epi2me_setup.workingDirectory)
+}
+
+/// Reads the Desktop `config.json` at `path_buf` so it can be pointed at
+/// `working_dir`.
+///
+/// Fails without `force` if the file already points somewhere else. A missing
+/// file reads as empty.
+pub fn check_working_directory(
+    path_buf: &PathBuf,
+    working_dir: &Path,
+    force: &bool,
+) -> Result<Map<String, Value>, Epi4youError> {
+    let working_dir = working_dir.to_string_lossy();
+    let mut config = Map::new();
+
+    if path_buf.exists() {
+        let content = fs::read_to_string(path_buf)
+            .map_err(|err| Epi4youError::FailedToReadPath(path_buf.clone(), err.into()))?;
+        config = serde_json::from_str(&content)
+            .map_err(|err| Epi4youError::FailedToParseFileContent(err.into()))?;
+
+        let current = config.get("workingDirectory").and_then(Value::as_str);
+        if current.is_some_and(|current| current != working_dir) && !*force {
+            log::error!(
+                "[{}] already points at [{}] - use `--force`?",
+                path_buf.display(),
+                current.unwrap_or_default()
+            );
+            return Err(Epi4youError::FileAlreadyExistsUnforcedExecution(
+                path_buf.clone(),
+            ));
+        }
+    }
+    Ok(config)
+}
+
+/// Points the Desktop `config.json` at `path_buf` to `working_dir`, keeping
+/// its other keys.
+pub fn set_working_directory(
+    path_buf: &PathBuf,
+    working_dir: &Path,
+    force: &bool,
+) -> Result<(), Epi4youError> {
+    let mut config = check_working_directory(path_buf, working_dir, force)?;
+    let working_dir = working_dir.to_string_lossy().into_owned();
+    if let Some(parent) = path_buf.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| Epi4youError::FailedToCreateFolder(parent.to_path_buf(), err.into()))?;
+    }
+
+    log::info!(
+        "json written to [{}] [workDir={}]",
+        path_buf.display(),
+        working_dir
+    );
+    config.insert(String::from("workingDirectory"), Value::String(working_dir));
+    let serialized = serde_json::to_string_pretty(&config)
+        .map_err(|err| Epi4youError::FailedToParseFileContent(err.into()))?;
+    fs::write(path_buf, serialized)
+        .map_err(|err| Epi4youError::FailedToWritePath(path_buf.clone(), err.into()))?;
 
-    println!("\tjson parsed [workDir={}]", epi2me_setup.workingDirectory);
-    epi2me_setup.workingDirectory
+    Ok(())
 }
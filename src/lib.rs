@@ -0,0 +1,214 @@
+//! Library interface to `epi4you`.
+//!
+//! The `epi4you` binary is a thin command-line layer over this crate, so other
+//! tooling can discover EPI2ME installations, package CLI Nextflow runs and
+//! import `.2me` archives without shelling out. Nothing here prints unless
+//! asked to: results are returned to the caller, failures are reported as
+//! [`Epi4youError`] and progress is written through the `log` facade, with
+//! progress bars only after [`progress::set_draw_bars`].
+//!
+//! The EPI2ME installation to work with is passed in explicitly, together with
+//! how many `app.db` backups it keeps and how long writes wait for Desktop to
+//! exit, so one process can serve several; [`find_db`] discovers the one
+//! Desktop uses and [`Epi2meSetup::from_root`] builds any other. Progress bars
+//! are the only process-wide setting.
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//!
+//! let epi2me = epi4you::Epi2meSetup::from_root(&PathBuf::from("/data/epi2me"));
+//! let manifest = epi4you::export_nextflow_run(
+//!     epi2me.as_ref(),
+//!     PathBuf::from("/data/runs"),
+//!     None,
+//!     "clever_ampere",
+//!     "/tmp/clever_ampere.2me.tar",
+//!     &epi4you::BundleOptions::default(),
+//! )?;
+//! println!("{} files packaged", manifest.filecount);
+//! # Ok::<(), epi4you::Epi4youError>(())
+//! ```
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub mod app_db;
+pub mod app_db_backup;
+pub mod app_db_lock;
+pub mod epi2me_db;
+pub mod epi2me_desktop_analysis;
+pub mod epi2me_workflow;
+pub mod epi4you;
+pub mod epi4you_errors;
+pub mod nextflow_log_parser;
+pub mod progress;
+pub mod provenance;
+pub mod tempdir;
+pub mod xmanifest;
+
+mod app_db_schema;
+mod bundle;
+mod epi2me_tar;
+mod json;
+
+pub mod nextflow {
+    pub mod nextflow_analysis;
+    pub mod nextflow_command;
+    pub mod nextflow_execution;
+    pub mod nextflow_history;
+    pub mod nextflow_log_item;
+    pub mod nextflow_output_dir;
+    pub mod nextflow_params;
+    pub mod nextflow_progress;
+    pub mod nextflow_reports;
+    pub mod nextflow_resources;
+    pub mod nextflow_timeline;
+    pub mod nextflow_toolkit;
+    pub mod nextflow_trace;
+    pub mod nextflow_watch;
+    pub mod nextflow_work;
+}
+
+pub use app_db::Epi2MeAnalysis;
+pub use epi2me_db::{find_db, Epi2meSetup};
+pub use epi2me_desktop_analysis::Epi2meDesktopAnalysis;
+pub use epi4you_errors::Epi4youError;
+pub use nextflow::nextflow_resources::ResourceSummary;
+pub use nextflow::nextflow_timeline::NextflowTimeline;
+pub use nextflow::nextflow_toolkit::{BundleOptions, NextFlowResultFolder};
+pub use xmanifest::Epi2MeManifest;
+
+/// Folders Desktop expects to find below its data directory.
+const EPI2ME_FOLDERS: [&str; 3] = ["instances", "workflows", "import_export_4you"];
+
+/// Packages the CLI Nextflow run `run_name` found in `nxf_work` as a `.2me`
+/// archive at `twome`.
+///
+/// `nxf_bin` is only used when the folder has no readable
+/// `.nextflow/history`. The run is staged in, and its paths recorded relative
+/// to, `epi2me` when given. The manifest written into the archive is returned.
+pub fn export_nextflow_run(
+    epi2me: Option<&Epi2meSetup>,
+    nxf_work: PathBuf,
+    nxf_bin: Option<String>,
+    run_name: &str,
+    twome: &str,
+    options: &BundleOptions,
+) -> Result<Epi2MeManifest, Epi4youError> {
+    let folder = NextFlowResultFolder::init(nxf_work, nxf_bin)?;
+    let wf_analysis = folder.verify_cli_entity(String::from(run_name))?;
+    let temp_dir = tempdir::get_tempdir(epi2me)?;
+    folder.bundle_cli_run(epi2me, &temp_dir, wf_analysis, twome, options)
+}
+
+/// Imports the CLI Nextflow run `run_name` found in `nxf_work` straight into
+/// the EPI2ME installation `epi2me`, without writing an archive.
+///
+/// With `move_outputs` the run's output files are moved into the new instance
+/// rather than copied. The registered analysis is returned.
+pub fn import_nextflow_run(
+    epi2me: &Epi2meSetup,
+    nxf_work: PathBuf,
+    nxf_bin: Option<String>,
+    run_name: &str,
+    options: &BundleOptions,
+    move_outputs: bool,
+) -> Result<Epi2MeAnalysis, Epi4youError> {
+    let folder = NextFlowResultFolder::init(nxf_work, nxf_bin)?;
+    let wf_analysis = folder.verify_cli_entity(String::from(run_name))?;
+    let temp_dir = tempdir::get_tempdir(Some(epi2me))?;
+    folder.import_cli_run(epi2me, &temp_dir, wf_analysis, options, move_outputs)
+}
+
+/// Parses the `.nextflow.log` of the CLI run `run_name` found in `nxf_work`
+/// into a timeline of typed events.
+///
+/// `nxf_log` names the log to read when the search would pick the wrong one.
+pub fn nextflow_timeline(
+    nxf_work: PathBuf,
+    nxf_bin: Option<String>,
+    run_name: &str,
+    nxf_log: Option<&Path>,
+) -> Result<NextflowTimeline, Epi4youError> {
+    let folder = NextFlowResultFolder::init(nxf_work, nxf_bin)?;
+    let wf_analysis = folder.verify_cli_entity(String::from(run_name))?;
+    folder.timeline(&wf_analysis, nxf_log)
+}
+
+/// Imports a `.2me` archive into the EPI2ME installation `epi2me`.
+///
+/// The archive is verified and unpacked into a temporary directory before
+/// anything local is modified. The analyses registered in `app.db` are
+/// returned.
+pub fn import_archive(
+    epi2me: &Epi2meSetup,
+    twome: PathBuf,
+    force: bool,
+) -> Result<Vec<Epi2MeAnalysis>, Epi4youError> {
+    if !twome.exists() {
+        return Err(Epi4youError::RequiredPathMissing(twome));
+    } else if twome.is_dir() {
+        return Err(Epi4youError::FolderFoundWhenFileExpected(twome));
+    }
+
+    let temp_dir = tempdir::get_tempdir(Some(epi2me))?;
+    let mut manifest = Epi2MeManifest::from_tarball(twome.clone())?;
+    manifest.unpack_container_content(&temp_dir.path, &twome, &force)?;
+    manifest.process_container_content(epi2me, &temp_dir.path)
+}
+
+/// Lays down an empty EPI2ME data directory at `root` and points the Desktop
+/// `config.json` at it.
+///
+/// Running this again is harmless: existing folders and tables are kept. An
+/// existing `config.json` that points elsewhere is only repointed with
+/// `force`, and is checked before anything is created so that a refused init
+/// leaves nothing behind.
+pub fn init_epi2me(
+    root: &Path,
+    config: &PathBuf,
+    force: bool,
+) -> Result<Epi2meSetup, Epi4youError> {
+    if root.is_file() {
+        return Err(Epi4youError::FileFoundWhenFolderExpected(
+            root.to_path_buf(),
+        ));
+    }
+    let root = resolve_new_path(root)
+        .map_err(|err| Epi4youError::FailedToReadPath(root.to_path_buf(), err.into()))?;
+    json::check_working_directory(config, &root, &force)?;
+
+    log::info!("initialising EPI2ME data directory at [{}]", root.display());
+
+    for folder in EPI2ME_FOLDERS {
+        let path = root.join(folder);
+        fs::create_dir_all(&path)
+            .map_err(|err| Epi4youError::FailedToCreateFolder(path.clone(), err.into()))?;
+        log::info!("folder ready at [{}]", path.display());
+    }
+
+    app_db::create_app_db(&root.join("app.db"))?;
+    json::set_working_directory(config, &root, &force)?;
+
+    let setup =
+        Epi2meSetup::from_root(&root).ok_or(Epi4youError::RequiredPathMissing(root.clone()))?;
+    log::info!(
+        "EPI2ME data directory ready at [{}]",
+        setup.epi2path.display()
+    );
+    Ok(setup)
+}
+
+/// Makes `path` absolute, resolving symlinks and `..` in the part that exists,
+/// so a folder about to be created is named as it will be once it exists.
+fn resolve_new_path(path: &Path) -> std::io::Result<PathBuf> {
+    let absolute = std::path::absolute(path)?;
+    let existing = absolute
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Path::new("/"));
+    let missing = absolute.strip_prefix(existing).unwrap_or(Path::new(""));
+    Ok(fs::canonicalize(existing)?.join(missing))
+}
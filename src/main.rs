@@ -1,106 +1,216 @@
-use clap::{Arg, Command};
+use std::{error::Error, path::PathBuf, time::Duration};
+
+use clap::{value_parser, Arg, ArgAction, Command};
+use config::manage_config;
 use create_2me::create_from_cli_run;
+use database::manage_app_db;
 use env_logger::Env;
-use epi4you_errors::Epi4youError;
+use epi2me_options::Epi2meOptions;
+use epi4you::{app_db_backup, epi2me_db, epi4you as about, progress, Epi4youError};
+use epi4you_config::Config;
 use importer::import_from_2me;
+use init::init_epi2me;
 
-mod app_db;
-mod bundle;
 mod dataframe;
-mod epi2me_db;
-mod epi2me_tar;
-mod json;
-mod provenance;
-mod tempdir;
-
-mod xmanifest;
-
-mod epi2me_desktop_analysis;
-mod epi2me_workflow;
-mod nextflow_log_parser;
+mod epi2me_options;
+mod epi4you_config;
+mod output;
 
-////////////////
-
-pub mod epi4you;
-pub mod epi4you_errors;
+mod config {
+    pub mod manage_config;
+}
 
-pub mod create_2me {
+mod create_2me {
     pub mod create_from_cli_run;
 }
 
-pub mod importer {
+mod database {
+    pub mod manage_app_db;
+}
+
+mod importer {
     pub mod import_from_2me;
 }
 
-pub mod nextflow {
-    pub mod nextflow_analysis;
-    pub mod nextflow_log_item;
-    pub mod nextflow_progress;
-    pub mod nextflow_toolkit;
+mod init {
+    pub mod init_epi2me;
 }
 
+/// Global argument naming an explicit EPI2ME root directory.
+const EPI2ME_ROOT: &str = "epi2me_root";
+/// Global argument setting how many `app.db` backups are retained.
+const KEEP_BACKUPS: &str = "keep_backups";
+/// Global argument to wait for EPI2ME Desktop to exit before writing.
+const WAIT: &str = "wait";
+/// Seconds `--wait` waits for Desktop to exit when given without a value.
+const DEFAULT_WAIT_SECONDS: &str = "600";
+/// Global argument selecting how command results are written.
+const OUTPUT: &str = "output";
+
 #[tokio::main]
 async fn main() {
     let env = Env::default()
         .filter_or("MY_LOG_LEVEL", "debug")
         .write_style_or("MY_LOG_STYLE", "always");
-    env_logger::init_from_env(env);
+    let logger = env_logger::Builder::from_env(env).build();
+    log::set_max_level(logger.filter());
+    if log::set_boxed_logger(Box::new(ProgressLogger(logger))).is_err() {
+        eprintln!("unable to initialise logging");
+    }
 
-    let mut temp_dir = match tempdir::get_tempdir() {
-        Ok(temp_dir) => temp_dir,
-        Err(err) => {
-            eprintln!("error creating tempdir: {err:?}");
-            std::process::exit(1);
+    if let Err(err) = run().await {
+        if !output::emit_error(&err) {
+            report_error(&err);
         }
-    };
+        std::process::exit(err.exit_code());
+    }
+}
+
+/// Logger that hides active progress bars while a record is written.
+struct ProgressLogger(env_logger::Logger);
 
-    if let Err(err) = run(&mut temp_dir).await {
-        eprintln!("{err:?}");
-        std::process::exit(1);
+impl log::Log for ProgressLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.0.matches(record) {
+            progress::multi_progress().suspend(|| self.0.log(record));
+        }
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+/// Writes an error and the chain of errors that caused it to stderr.
+fn report_error(err: &Epi4youError) {
+    eprintln!("error: {err}");
+    let mut source = err.source();
+    while let Some(cause) = source {
+        eprintln!("  caused by: {cause}");
+        source = cause.source();
     }
 }
 
-async fn run(temp_dir: &mut tempdir::TempDir) -> Result<(), Epi4youError> {
-    let use_args: Vec<Arg> = Vec::<Arg>::new();
+async fn run() -> Result<(), Epi4youError> {
+    let use_args: Vec<Arg> = vec![
+        Arg::new(EPI2ME_ROOT)
+            .long("epi2me-root")
+            .help(
+                "EPI2ME data directory (or Desktop config.json) to use instead of the one in $HOME",
+            )
+            .env(epi2me_db::EPI2ME_ROOT_ENV)
+            .global(true)
+            .action(ArgAction::Set)
+            .value_parser(value_parser!(PathBuf)),
+        Arg::new(KEEP_BACKUPS)
+            .long("keep-backups")
+            .help("number of app.db backups to retain")
+            .env(app_db_backup::BACKUP_RETENTION_ENV)
+            .global(true)
+            .action(ArgAction::Set)
+            .value_parser(value_parser!(usize)),
+        Arg::new(WAIT)
+            .long("wait")
+            .value_name("seconds")
+            .help("wait up to this long (default 600s) for EPI2ME Desktop to exit instead of refusing to modify app.db")
+            .global(true)
+            .action(ArgAction::Set)
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value(DEFAULT_WAIT_SECONDS)
+            .value_parser(value_parser!(u64).range(1..)),
+        Arg::new(OUTPUT)
+            .long("output")
+            .help("format for command results written to stdout")
+            .global(true)
+            .action(ArgAction::Set)
+            .default_value("table")
+            .value_parser(output::OUTPUT_FORMATS),
+    ];
     let mut subcmds: Vec<Command> = Vec::<Command>::new();
 
     subcmds.push(create_from_cli_run::get_cli_setup());
     subcmds.push(import_from_2me::get_cli_setup());
+    subcmds.push(init_epi2me::get_cli_setup());
+    subcmds.push(manage_app_db::get_cli_setup());
+    subcmds.push(manage_config::get_cli_setup());
 
-    let app = Command::new(epi4you::APPLICATION_NAME)
+    let app = Command::new(about::APPLICATION_NAME)
         .subcommand_required(false)
-        .version(epi4you::APPLICATION_VERSION)
-        .author(epi4you::APPLICATION_AUTHOR)
-        .about(epi4you::APPLICATION_ABOUT)
-        .long_about(epi4you::APPLICATION_DESCRIPTION)
+        .version(about::APPLICATION_VERSION)
+        .author(about::APPLICATION_AUTHOR)
+        .about(about::APPLICATION_ABOUT)
+        .long_about(about::APPLICATION_DESCRIPTION)
         .args(use_args)
         .subcommands(subcmds.clone());
 
-    match app.try_get_matches() {
-        Ok(matches) => match matches.subcommand() {
-            Some((create_from_cli_run::NEXTFLOW_RUN, sub_matches)) => {
-                log::debug!(
-                    "subcommand [{}] has been called",
-                    create_from_cli_run::NEXTFLOW_RUN
-                );
-                create_from_cli_run::process_clicapture_command(sub_matches, temp_dir)
-            }
-            Some((import_from_2me::IMPORT2ME, sub_matches)) => {
-                log::debug!(
-                    "subcommand [{}] has been called",
-                    import_from_2me::IMPORT2ME
-                );
-                import_from_2me::process_2me_import_command(sub_matches, temp_dir).await
-            }
-            Some((name, _)) => {
-                log::error!("unexpected subcommand [{name}]");
-                Err(Epi4youError::MalformedCLISetup)
-            }
-            None => Ok(()),
-        },
-        Err(err) => {
-            err.print().ok();
-            Ok(())
+    let matches = match app.try_get_matches() {
+        Ok(matches) => matches,
+        // clap exits with 0 for --help/--version and 2 for usage errors
+        Err(err) => err.exit(),
+    };
+
+    let config = Config::load()?;
+    progress::set_draw_bars(true);
+
+    let epi2me = Epi2meOptions {
+        root: matches
+            .get_one::<PathBuf>(EPI2ME_ROOT)
+            .cloned()
+            .or_else(|| config.epi2me_root()),
+        keep_backups: matches
+            .get_one::<usize>(KEEP_BACKUPS)
+            .copied()
+            .or_else(|| config.keep_backups()),
+        wait: matches
+            .get_one::<u64>(WAIT)
+            .map(|seconds| Duration::from_secs(*seconds)),
+    };
+    let output_format = if epi4you_config::is_explicit(&matches, OUTPUT) {
+        matches.get_one::<String>(OUTPUT).cloned()
+    } else {
+        config.output()
+    };
+    if let Some(format) = output_format.and_then(|format| output::OutputFormat::from_name(&format))
+    {
+        output::set_output_format(format);
+    }
+
+    match matches.subcommand() {
+        Some((create_from_cli_run::NEXTFLOW_RUN, sub_matches)) => {
+            log::debug!(
+                "subcommand [{}] has been called",
+                create_from_cli_run::NEXTFLOW_RUN
+            );
+            create_from_cli_run::process_clicapture_command(sub_matches, &config, &epi2me)
+        }
+        Some((import_from_2me::IMPORT2ME, sub_matches)) => {
+            log::debug!(
+                "subcommand [{}] has been called",
+                import_from_2me::IMPORT2ME
+            );
+            import_from_2me::process_2me_import_command(sub_matches, &config, &epi2me).await
+        }
+        Some((init_epi2me::INIT, sub_matches)) => {
+            log::debug!("subcommand [{}] has been called", init_epi2me::INIT);
+            init_epi2me::process_init_command(sub_matches, &epi2me)
+        }
+        Some((manage_app_db::DATABASE, sub_matches)) => {
+            log::debug!("subcommand [{}] has been called", manage_app_db::DATABASE);
+            manage_app_db::process_database_command(sub_matches, &epi2me)
+        }
+        Some((manage_config::CONFIG, sub_matches)) => {
+            log::debug!("subcommand [{}] has been called", manage_config::CONFIG);
+            manage_config::process_config_command(sub_matches, &config)
+        }
+        Some((name, _)) => {
+            log::error!("unexpected subcommand [{name}]");
+            Err(Epi4youError::MalformedCLISetup)
         }
+        None => Ok(()),
     }
 }
@@ -6,18 +6,26 @@
 //! shape for `epi4you` imports and exports.
 
 use std::{
+    cmp::Reverse,
     collections::HashMap,
     fs,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
 use glob::glob;
+use serde_json::{json, Value};
 
 use crate::epi4you_errors::Epi4youError;
 
 use super::{
+    nextflow_command::NextflowCommand,
     nextflow_log_item::NxfLogItem,
+    nextflow_output_dir::{resolve_output_dir, OutputDirStrategy},
+    nextflow_params,
     nextflow_progress::{ProgressItem, ProgressJson},
+    nextflow_timeline::{LogEvent, NextflowTimeline},
+    nextflow_trace::{self, TraceRecord},
 };
 
 /// Resolved view of one Nextflow analysis on disk.
@@ -28,27 +36,41 @@ pub struct NextflowAnalysis {
     wf_analysis: NxfLogItem,
     src_dir: PathBuf,
     folder: PathBuf,
+    strategy: OutputDirStrategy,
+    log_path: PathBuf,
+    nf_log: String,
 }
 
 impl NextflowAnalysis {
-    /// Resolves the output directory associated with a log row.
+    /// Locates the log of a run and resolves its output directory.
     ///
-    /// The preferred source of truth is the original command line, especially
-    /// `--out_dir` / `--out-dir`. If that cannot be recovered, we fall back to
-    /// the common EPI2ME-style `output/` convention.
-    pub fn init(wf_analysis: NxfLogItem, analysis_folder: PathBuf) -> Result<Self, Epi4youError> {
+    /// `nxf_log` and `out_dir` override log selection and output-directory
+    /// detection; otherwise [`find_run_log`] and [`resolve_output_dir`] decide.
+    pub fn init(
+        wf_analysis: NxfLogItem,
+        analysis_folder: PathBuf,
+        out_dir: Option<&Path>,
+        nxf_log: Option<&Path>,
+    ) -> Result<Self, Epi4youError> {
         log::info!("processing command [{:?}]", &wf_analysis.command);
 
-        let candidate = resolve_analysis_dir(&wf_analysis.command, &analysis_folder)
-            .or_else(|| fallback_output_dir(&analysis_folder))
-            .ok_or(Epi4youError::NextflowAnalysisFolderNotFound)?;
+        let (log_path, nf_log) = find_run_log(&analysis_folder, &wf_analysis, nxf_log)?;
+        let command = NextflowCommand::parse(&wf_analysis.command);
+        let (candidate, strategy) =
+            resolve_output_dir(&command, &nf_log, &analysis_folder, out_dir).ok_or_else(|| {
+                log::error!("unable to find the output folder - name it with `--out-dir`");
+                Epi4youError::NextflowAnalysisFolderNotFound
+            })?;
 
-        log::info!("using analysis folder [{:?}]", candidate);
+        log::info!("using analysis folder [{:?}] ({strategy})", candidate);
 
         Ok(NextflowAnalysis {
             wf_analysis,
             src_dir: analysis_folder,
             folder: candidate,
+            strategy,
+            log_path,
+            nf_log,
         })
     }
 
@@ -57,51 +79,24 @@ impl NextflowAnalysis {
         self.folder.clone()
     }
 
-    /// Locates the `.nextflow.log*` file that belongs to this run.
+    /// Returns how the analysis output directory was found.
+    pub fn get_output_dir_strategy(&self) -> OutputDirStrategy {
+        self.strategy
+    }
+
+    /// Copies the `.nextflow.log*` file that belongs to this run into the
+    /// staging area under the stable name `nextflow.log`.
     ///
-    /// Multiple logs may exist after repeated runs in the same directory. We
-    /// choose the one that mentions the specific `run_name` and then copy it
-    /// into the temporary staging area under the stable name `nextflow.log`.
+    /// Multiple logs may exist after repeated runs in the same directory; the
+    /// one chosen is the one that mentions the specific `run_name`. Its text
+    /// is returned.
     pub fn locate_nextflow_log(&self, tmp_dir: &PathBuf) -> Result<String, Epi4youError> {
-        log::info!("locating nextflow logs ...");
-
-        let mut candidate_logs: Vec<String> = Vec::new();
-        let mut candidate_pbs: Vec<PathBuf> = Vec::new();
-
-        let mut glob_fish_str = self.src_dir.to_string_lossy().into_owned();
-        glob_fish_str.push(std::path::MAIN_SEPARATOR);
-        glob_fish_str.push_str(".nextflow.log*");
-
-        for entry in glob(&glob_fish_str).map_err(|_| Epi4youError::FailedToParseFileContent)? {
-            if let Ok(cand_logfile) = entry {
-                let log = get_matched_nexflow_log(&cand_logfile, &self.wf_analysis.run_name);
-                if let Some(log) = log {
-                    candidate_logs.push(log);
-                    candidate_pbs.push(cand_logfile);
-                }
-            }
-        }
-
-        match candidate_logs.len() {
-            0 => {
-                log::error!(
-                    "failed to locate appropriately tagged logfile - have you been housekeeping?"
-                );
-                Err(Epi4youError::FileSelectionFailedFileNotFound)
-            }
-            1 => {
-                let mut target = tmp_dir.clone();
-                target.push("nextflow.log");
-                fs::copy(&candidate_pbs[0], &target)
-                    .map_err(|_| Epi4youError::FailedToWritePath(target.clone()))?;
-                log::info!("populating nextflow.log to [{:?}]", target);
-                Ok(candidate_logs.remove(0))
-            }
-            _ => {
-                log::error!("log file selection is ambiguous - more than one match");
-                Err(Epi4youError::FileSelectionIsAmbiguous)
-            }
-        }
+        let mut target = tmp_dir.clone();
+        target.push("nextflow.log");
+        fs::copy(&self.log_path, &target)
+            .map_err(|err| Epi4youError::FailedToWritePath(target.clone(), err.into()))?;
+        log::info!("populating nextflow.log to [{:?}]", target);
+        Ok(self.nf_log.clone())
     }
 
     /// Distills the full Nextflow log into the subset EPI2ME-style metadata
@@ -147,16 +142,27 @@ impl NextflowAnalysis {
 
         let mut target = tmp_dir.clone();
         target.push("nextflow.stdout");
-        fs::write(&target, &cache).map_err(|_| Epi4youError::FailedToWritePath(target.clone()))?;
-        println!("populating nextflow.stdout to [{:?}]", target);
+        fs::write(&target, &cache)
+            .map_err(|err| Epi4youError::FailedToWritePath(target.clone(), err.into()))?;
+        log::info!("populating nextflow.stdout to [{:?}]", target);
         Ok(cache)
     }
 
-    /// Builds a compact `progress.json` file from submitted-process lines.
+    /// Reads the execution trace this run wrote below its output folder.
     ///
-    /// This mirrors the sort of task summary EPI2ME Desktop presents in its UI.
-    /// It is intentionally lossy: the goal is to recover a useful final
-    /// completed-process picture rather than every transient scheduler event.
+    /// See [`nextflow_trace::find_run_trace`] for how a trace is tied to the
+    /// run.
+    pub fn find_trace(&self) -> Option<Vec<TraceRecord>> {
+        nextflow_trace::find_run_trace(&self.folder, &self.wf_analysis, &self.nf_log)
+    }
+
+    /// Builds the `progress.json` file EPI2ME Desktop presents in its UI.
+    ///
+    /// When the run's execution trace is found, per-process totals, completed,
+    /// failed and cached counts are taken from it. Otherwise we fall back to
+    /// counting submitted-process lines in the log, which is lossy: every
+    /// submitted task is assumed to have completed. Processes are listed in
+    /// the order the run first reached them.
     pub fn prepare_progress_json(
         &self,
         nextflow_stdout: &str,
@@ -165,10 +171,16 @@ impl NextflowAnalysis {
     ) -> Result<PathBuf, Epi4youError> {
         let mut progress = ProgressJson {
             name: ulid_str.to_owned(),
-            key: HashMap::new(),
+            key: Vec::new(),
         };
 
-        let mut process_counter: HashMap<String, u16> = HashMap::new();
+        if let Some(records) = self.find_trace() {
+            progress.key = nextflow_trace::progress_from_trace(&records);
+            return write_progress_json(&progress, temp_dir);
+        }
+        log::info!("no execution trace found - deriving progress from the log");
+
+        let mut process_counter: HashMap<String, u32> = HashMap::new();
         let mut bfx_process: Vec<String> = Vec::new();
 
         let subproc = "Submitted process >";
@@ -182,7 +194,7 @@ impl NextflowAnalysis {
                 }
 
                 if let Some(count) = process_counter.get_mut(line) {
-                    *count += 1;
+                    *count = count.saturating_add(1);
                 } else {
                     process_counter.insert(line.to_owned(), 1);
                     bfx_process.push(line.to_owned());
@@ -197,128 +209,248 @@ impl NextflowAnalysis {
                 tag: String::from("null"),
                 total: *val,
                 complete: *val,
+                failed: 0,
+                cached: 0,
             };
-            progress.key.insert(key, pi);
+            progress.key.push((key, pi));
         }
 
-        let serialized =
-            serde_json::to_string(&progress).map_err(|_| Epi4youError::FailedToParseFileContent)?;
-        let mut target = temp_dir.clone();
-        target.push("progress.json");
-        fs::write(&target, serialized)
-            .map_err(|_| Epi4youError::FailedToWritePath(target.clone()))?;
-        println!("populating progress.json to [{:?}]", target);
-        Ok(target)
+        write_progress_json(&progress, temp_dir)
     }
-}
-
-/// Attempts to resolve the analysis directory from the original CLI command.
-fn resolve_analysis_dir(command: &str, analysis_folder: &Path) -> Option<PathBuf> {
-    let output_dir = parse_output_dir(command)?;
-    let candidate = analysis_folder.join(output_dir);
 
-    if candidate.exists() && candidate.is_dir() {
-        Some(candidate)
-    } else {
-        None
+    /// Writes the `params.json` and `launch.json` Desktop keeps for every
+    /// analysis, so that imported CLI runs show their inputs in the GUI.
+    ///
+    /// `params.json` holds the recovered workflow parameters; `launch.json`
+    /// records how the run was started, with the same parameters embedded.
+    pub fn prepare_params_json(
+        &self,
+        nf_log: &str,
+        temp_dir: &Path,
+        ulid_str: &str,
+    ) -> Result<(), Epi4youError> {
+        let command = NextflowCommand::parse(&self.wf_analysis.command);
+        let params = nextflow_params::recover_params(&command, nf_log, &self.src_dir);
+        log::info!("recovered {} workflow parameters", params.len());
+
+        let launch = json!({
+            "id": ulid_str,
+            "name": self.wf_analysis.run_name,
+            "workflow": command.pipeline,
+            "revision": self.wf_analysis.revision_id,
+            "profile": command.option("-profile"),
+            "sessionId": self.wf_analysis.session_id,
+            "command": self.wf_analysis.command,
+            "params": params,
+        });
+        write_json(&Value::Object(params), temp_dir, "params.json")?;
+        write_json(&launch, temp_dir, "launch.json")?;
+        Ok(())
     }
 }
 
-/// Falls back to the common `output/` directory used by many EPI2ME workflows.
-fn fallback_output_dir(analysis_folder: &Path) -> Option<PathBuf> {
-    let candidate = analysis_folder.join("output");
-    if candidate.exists() && candidate.is_dir() {
-        Some(candidate)
-    } else {
-        None
-    }
+/// Writes a JSON document into the staging folder.
+fn write_json(value: &Value, temp_dir: &Path, name: &str) -> Result<PathBuf, Epi4youError> {
+    let serialized = serde_json::to_string_pretty(value)
+        .map_err(|err| Epi4youError::FailedToParseFileContent(err.into()))?;
+    let target = temp_dir.join(name);
+    fs::write(&target, serialized)
+        .map_err(|err| Epi4youError::FailedToWritePath(target.clone(), err.into()))?;
+    log::info!("populating {name} to [{:?}]", target);
+    Ok(target)
+}
+
+/// Writes `progress.json` into the staging folder.
+fn write_progress_json(progress: &ProgressJson, temp_dir: &Path) -> Result<PathBuf, Epi4youError> {
+    let serialized = serde_json::to_string(progress)
+        .map_err(|err| Epi4youError::FailedToParseFileContent(err.into()))?;
+    let target = temp_dir.join("progress.json");
+    fs::write(&target, serialized)
+        .map_err(|err| Epi4youError::FailedToWritePath(target.clone(), err.into()))?;
+    log::info!("populating progress.json to [{:?}]", target);
+    Ok(target)
 }
 
-/// Parses supported output-directory flags from a Nextflow command line.
+/// Finds the `.nextflow.log*` file in `src_dir` written by `wf_analysis`.
 ///
-/// Supporting both `--out_dir` and `--out-dir` lets the code tolerate
-/// historical differences between workflow parameter styles.
-fn parse_output_dir(command: &str) -> Option<&str> {
-    const KEYS: [&str; 4] = ["--out_dir=", "--out-dir=", "--out_dir", "--out-dir"];
-
-    for token in command.split_whitespace() {
-        if let Some(value) = token
-            .strip_prefix(KEYS[0])
-            .or_else(|| token.strip_prefix(KEYS[1]))
-        {
-            return (!value.is_empty()).then_some(value);
+/// A resumed run's log mentions the run it resumed, and every run of a session
+/// shares its UUID, so candidates are ranked by [`log_rank`] and ties go to the
+/// most recently modified log. A log that only mentions the run name is used,
+/// with a warning, when it is the only one to do so. `nxf_log` names the log
+/// outright, relative to the current directory. Returns the path together with
+/// the log text.
+pub fn find_run_log(
+    src_dir: &Path,
+    wf_analysis: &NxfLogItem,
+    nxf_log: Option<&Path>,
+) -> Result<(PathBuf, String), Epi4youError> {
+    if let Some(nxf_log) = nxf_log {
+        let path = std::path::absolute(nxf_log)
+            .map_err(|err| Epi4youError::FailedToReadPath(nxf_log.to_path_buf(), err.into()))?;
+        if !path.is_file() {
+            return Err(Epi4youError::RequiredPathMissing(path));
         }
+        let content = fs::read_to_string(&path)
+            .map_err(|err| Epi4youError::FailedToReadPath(path.clone(), err.into()))?;
+        if log_rank(&content, wf_analysis).is_none() {
+            log::warn!(
+                "[{}] neither launches run [{}] nor shares its session - using it anyway",
+                path.display(),
+                wf_analysis.run_name
+            );
+        }
+        return Ok((path, content));
+    }
+
+    log::info!("locating nextflow logs ...");
+
+    let mut candidates: Vec<(u8, Option<SystemTime>, PathBuf, String)> = Vec::new();
+    let mut mentions: Vec<(PathBuf, String)> = Vec::new();
+
+    let mut glob_fish_str = src_dir.to_string_lossy().into_owned();
+    glob_fish_str.push(std::path::MAIN_SEPARATOR);
+    glob_fish_str.push_str(".nextflow.log*");
+
+    for cand_logfile in glob(&glob_fish_str)
+        .map_err(|err| Epi4youError::FailedToParseFileContent(err.into()))?
+        .flatten()
+    {
+        let Ok(content) = fs::read_to_string(&cand_logfile) else {
+            continue;
+        };
+        if let Some(rank) = log_rank(&content, wf_analysis) {
+            let modified = cand_logfile
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .ok();
+            candidates.push((rank, modified, cand_logfile, content));
+        } else if content.contains(wf_analysis.run_name.trim()) {
+            mentions.push((cand_logfile, content));
+        }
+    }
+
+    if candidates.is_empty() && mentions.len() == 1 {
+        let (path, content) = mentions.remove(0);
+        log::warn!(
+            "[{}] only mentions run [{}] - using it as no log launches the run; name another with `--nxf-log`",
+            path.display(),
+            wf_analysis.run_name
+        );
+        return Ok((path, content));
     }
 
-    let mut tokens = command.split_whitespace();
-    while let Some(token) = tokens.next() {
-        if token == KEYS[2] || token == KEYS[3] {
-            return tokens
-                .next()
-                .map(|value| value.trim_matches('"').trim_matches('\''))
-                .filter(|value| !value.is_empty());
+    candidates.sort_by_key(|(rank, modified, ..)| Reverse((*rank, *modified)));
+    if candidates.len() > 1 {
+        log::info!(
+            "{} logs mention run [{}] - choosing [{}]; name another with `--nxf-log`",
+            candidates.len(),
+            wf_analysis.run_name,
+            candidates[0].2.display()
+        );
+    }
+    match candidates.into_iter().next() {
+        Some((_, _, path, content)) => Ok((path, content)),
+        None => {
+            log::error!(
+                "failed to locate appropriately tagged logfile - have you been housekeeping?"
+            );
+            Err(Epi4youError::FileSelectionFailedFileNotFound)
         }
     }
+}
 
-    None
+/// Scores how surely a log belongs to `wf_analysis`.
+///
+/// A `Launching ... [run_name]` line for the run is worth 2 and the run's
+/// session UUID 1. Logs with neither, including those that merely mention the
+/// run name, are `None`.
+fn log_rank(content: &str, wf_analysis: &NxfLogItem) -> Option<u8> {
+    let run_name = wf_analysis.run_name.trim();
+    let timeline = NextflowTimeline::parse(content);
+    let launched = timeline.events.iter().any(
+        |entry| matches!(&entry.event, LogEvent::Launch { run_name: name, .. } if name == run_name),
+    );
+    let same_session = !wf_analysis.session_id.is_empty()
+        && timeline.session_id() == Some(wf_analysis.session_id.trim());
+    let rank = 2 * u8::from(launched) + u8::from(same_session);
+    (rank > 0).then_some(rank)
 }
 
-/// Returns the full text of a candidate log if it belongs to the named run.
-fn get_matched_nexflow_log(cand_logfile: &PathBuf, run_name: &str) -> Option<String> {
-    let content = fs::read_to_string(cand_logfile).ok()?;
-    content.contains(run_name).then_some(content)
+/// Log fragments that show a run was interrupted rather than failing.
+const CANCEL_MARKERS: [&str; 4] = ["SIGINT", "SIGTERM", "SIGHUP", "Execution interrupted"];
+
+/// Maps a Nextflow history status onto the `status` Desktop stores in app.db.
+///
+/// `OK` runs are `COMPLETED` and `ERR` runs are `ERROR`, unless the log shows
+/// the run was interrupted by a signal, which Desktop presents as `STOPPED`.
+/// A run with no recorded status was killed before Nextflow could write one
+/// and is also `STOPPED`.
+pub fn desktop_status(nxf_status: &str, nf_log: &str) -> &'static str {
+    let cancelled = || CANCEL_MARKERS.iter().any(|marker| nf_log.contains(marker));
+    match nxf_status.trim() {
+        "OK" => "COMPLETED",
+        "ERR" if !cancelled() => "ERROR",
+        _ => "STOPPED",
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_output_dir, resolve_analysis_dir};
-    use std::{fs, path::PathBuf};
+    use std::fs;
+
+    use super::{desktop_status, find_run_log, log_rank};
+    use crate::{nextflow::nextflow_log_item::NxfLogItem, tempdir::unique_test_dir};
 
     #[test]
-    fn parses_output_dir_argument_with_equals() {
+    fn maps_nextflow_status_to_desktop_status() {
+        assert_eq!(desktop_status("OK", ""), "COMPLETED");
         assert_eq!(
-            parse_output_dir("nextflow run wf --out_dir=results"),
-            Some("results")
+            desktop_status("ERR", "ERROR ~ Error executing process"),
+            "ERROR"
         );
         assert_eq!(
-            parse_output_dir("nextflow run wf --out-dir=results"),
-            Some("results")
+            desktop_status("ERR", "Session aborted -- Cause: SIGINT"),
+            "STOPPED"
         );
+        assert_eq!(desktop_status("-", ""), "STOPPED");
     }
 
     #[test]
-    fn parses_output_dir_argument_with_separate_value() {
-        assert_eq!(
-            parse_output_dir("nextflow run wf --out_dir results -profile test"),
-            Some("results")
-        );
-        assert_eq!(
-            parse_output_dir("nextflow run wf --out-dir 'results' -profile test"),
-            Some("results")
-        );
+    fn ranks_launching_log_above_resumed_one() {
+        let run = NxfLogItem {
+            run_name: String::from("clever_ampere"),
+            session_id: String::from("sess-1"),
+            ..Default::default()
+        };
+        let own = "\
+Oct-18 10:00:02.000 [main] INFO  nextflow.cli.CmdRun - Launching `wf` [clever_ampere] DSL2 - revision: abc [master]
+Oct-18 10:00:03.000 [main] DEBUG nextflow.Session - Session UUID: sess-1
+";
+        let resumed = "\
+Oct-18 11:00:00.000 [main] DEBUG nextflow.cli.Launcher - $> nextflow run wf -resume clever_ampere
+Oct-18 11:00:02.000 [main] INFO  nextflow.cli.CmdRun - Launching `wf` [sad_turing] DSL2 - revision: abc [master]
+Oct-18 11:00:03.000 [main] DEBUG nextflow.Session - Session UUID: sess-1
+";
+        assert_eq!(log_rank(own, &run), Some(3));
+        assert_eq!(log_rank(resumed, &run), Some(1));
+        assert_eq!(log_rank("[clever_amperes]", &run), None);
+        assert_eq!(log_rank("nothing here", &run), None);
     }
 
     #[test]
-    fn resolves_existing_analysis_dir() {
-        let root = unique_test_dir("analysis-dir");
-        let results = root.join("results");
-        fs::create_dir_all(&results).unwrap();
-
-        let resolved = resolve_analysis_dir("nextflow run wf --out_dir results", &root);
-        assert_eq!(resolved, Some(results));
-
-        fs::remove_dir_all(root).unwrap();
-    }
+    fn takes_a_log_that_only_mentions_the_run_when_it_is_the_only_one() {
+        let tmp = unique_test_dir("run-log");
+        let run = NxfLogItem {
+            run_name: String::from("clever_ampere"),
+            session_id: String::from("sess-1"),
+            ..Default::default()
+        };
+        let mention = "Oct-18 11:00:00.000 [main] DEBUG nextflow.cli.Launcher - $> nextflow run wf -resume clever_ampere\n";
+        fs::write(tmp.path.join(".nextflow.log.1"), mention).unwrap();
+        let (path, _) = find_run_log(&tmp.path, &run, None).unwrap();
+        assert_eq!(path, tmp.path.join(".nextflow.log.1"));
 
-    fn unique_test_dir(prefix: &str) -> PathBuf {
-        let mut path = std::env::temp_dir();
-        path.push(format!(
-            "epi4you-{prefix}-{}",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_nanos()
-        ));
-        path
+        fs::write(tmp.path.join(".nextflow.log.2"), mention).unwrap();
+        assert!(find_run_log(&tmp.path, &run, None).is_err());
     }
 }
@@ -0,0 +1,275 @@
+//! Parsing of the `nextflow run` command line recorded for a run.
+//!
+//! The history keeps the command exactly as it was typed, so it is the most
+//! reliable record of which pipeline was launched, with which Nextflow options
+//! (`-profile`, `-c`, `-resume`, ...) and with which `--` workflow parameters.
+
+use serde_json::{Map, Value};
+
+/// Nextflow options that always take a value.
+const VALUE_OPTIONS: [&str; 22] = [
+    "-c",
+    "-C",
+    "-config",
+    "-profile",
+    "-params-file",
+    "-r",
+    "-revision",
+    "-w",
+    "-work-dir",
+    "-name",
+    "-hub",
+    "-user",
+    "-entry",
+    "-lib",
+    "-ansi-log",
+    "-cache",
+    "-qs",
+    "-queue-size",
+    "-main-script",
+    "-plugins",
+    "-log",
+    "-bucket-dir",
+];
+/// Nextflow options whose value may be omitted, e.g. `-resume` or `-with-report`.
+const OPTIONAL_VALUE_OPTIONS: [&str; 11] = [
+    "-resume",
+    "-with-report",
+    "-with-timeline",
+    "-with-dag",
+    "-with-trace",
+    "-with-docker",
+    "-with-singularity",
+    "-with-apptainer",
+    "-with-podman",
+    "-with-conda",
+    "-with-weblog",
+];
+
+/// A `nextflow run` command split into its parts.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NextflowCommand {
+    /// Pipeline as given: a repository, URL, local directory or script.
+    pub pipeline: Option<String>,
+    /// Single-dash Nextflow options in the order given, with their value.
+    pub options: Vec<(String, Option<String>)>,
+    /// Double-dash workflow parameters in the order given.
+    pub params: Vec<(String, String)>,
+}
+
+impl NextflowCommand {
+    /// Parses a command such as `nextflow -c extra.config run wf --in 'a b'`.
+    ///
+    /// Options given before `run` (like `-c`) are kept with the rest.
+    pub fn parse(command: &str) -> Self {
+        let tokens = split_command(command);
+        let mut parsed = NextflowCommand::default();
+        let mut seen_run = false;
+        let mut idx = 0;
+
+        while idx < tokens.len() {
+            let token = &tokens[idx];
+            let next = tokens.get(idx + 1);
+            idx += 1;
+
+            if let Some(param) = token.strip_prefix("--") {
+                if let Some((key, value)) = param.split_once('=') {
+                    parsed.params.push((key.to_owned(), value.to_owned()));
+                } else {
+                    let value = match next {
+                        Some(value) if is_param_value(value) => {
+                            idx += 1;
+                            value.clone()
+                        }
+                        _ => String::from("true"),
+                    };
+                    parsed.params.push((param.to_owned(), value));
+                }
+            } else if token.starts_with('-') && token.len() > 1 {
+                if let Some((option, value)) = token.split_once('=') {
+                    parsed
+                        .options
+                        .push((option.to_owned(), Some(value.to_owned())));
+                    continue;
+                }
+                let takes_value = VALUE_OPTIONS.contains(&token.as_str())
+                    || (OPTIONAL_VALUE_OPTIONS.contains(&token.as_str())
+                        && parsed.pipeline.is_some()
+                        && next.is_some_and(|value| !value.starts_with('-')));
+                let value = match next {
+                    Some(value) if takes_value => {
+                        idx += 1;
+                        Some(value.clone())
+                    }
+                    _ => None,
+                };
+                parsed.options.push((token.clone(), value));
+            } else if !seen_run {
+                seen_run = token == "run";
+            } else if parsed.pipeline.is_none() {
+                parsed.pipeline = Some(token.clone());
+            }
+        }
+        parsed
+    }
+
+    /// Returns the value of the last occurrence of a Nextflow option.
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(option, _)| option == name)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    /// Returns every value given for a repeatable option such as `-c`.
+    pub fn option_values(&self, name: &str) -> Vec<&str> {
+        self.options
+            .iter()
+            .filter(|(option, _)| option == name)
+            .filter_map(|(_, value)| value.as_deref())
+            .collect()
+    }
+
+    /// Returns true if the option was given, with or without a value.
+    pub fn has_option(&self, name: &str) -> bool {
+        self.options.iter().any(|(option, _)| option == name)
+    }
+
+    /// Returns the value of a workflow parameter, if given.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .rev()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the workflow parameters as JSON with their values typed.
+    pub fn params_json(&self) -> Map<String, Value> {
+        self.params
+            .iter()
+            .map(|(key, value)| (key.clone(), typed_value(value)))
+            .collect()
+    }
+}
+
+/// Converts a textual parameter value the way Nextflow does: booleans and
+/// numbers become JSON booleans and numbers, anything else stays a string.
+pub fn typed_value(value: &str) -> Value {
+    match value {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => value
+            .parse::<i64>()
+            .map(Value::from)
+            .ok()
+            .or_else(|| {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|number| number.is_finite())
+                    .map(Value::from)
+            })
+            .unwrap_or_else(|| Value::String(value.to_owned())),
+    }
+}
+
+/// Returns true if `token` is the value of the preceding `--param` rather than
+/// the next option; negative numbers are values.
+fn is_param_value(token: &str) -> bool {
+    !token.starts_with('-') || token.parse::<f64>().is_ok()
+}
+
+/// Splits a command line into words the way a POSIX shell would, honouring
+/// single quotes, double quotes and backslash escapes.
+pub fn split_command(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '"' => {
+                in_word = true;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => word.extend(chars.next()),
+                        _ => word.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                word.extend(chars.next());
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            _ => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{split_command, NextflowCommand};
+
+    #[test]
+    fn splits_quoted_words() {
+        assert_eq!(
+            split_command(r#"nextflow run wf --in "my reads/a b" --label 'x y' --z a\ b"#),
+            [
+                "nextflow",
+                "run",
+                "wf",
+                "--in",
+                "my reads/a b",
+                "--label",
+                "x y",
+                "--z",
+                "a b"
+            ]
+        );
+    }
+
+    #[test]
+    fn separates_pipeline_options_and_params() {
+        let command = NextflowCommand::parse(
+            "nextflow -c extra.config run epi2me-labs/wf-alignment -profile standard -resume -with-report report.html --out_dir output --threads 8 --verbose --ratio=0.5 --offset -1 -stub-run",
+        );
+        assert_eq!(
+            command.pipeline.as_deref(),
+            Some("epi2me-labs/wf-alignment")
+        );
+        assert_eq!(command.option_values("-c"), ["extra.config"]);
+        assert_eq!(command.option("-profile"), Some("standard"));
+        assert!(command.has_option("-resume"));
+        assert_eq!(command.option("-resume"), None);
+        assert_eq!(command.option("-with-report"), Some("report.html"));
+        assert!(command.has_option("-stub-run"));
+        assert_eq!(command.param("out_dir"), Some("output"));
+        assert_eq!(
+            serde_json::Value::Object(command.params_json()),
+            json!({"out_dir": "output", "threads": 8, "verbose": true, "ratio": 0.5, "offset": -1})
+        );
+    }
+}
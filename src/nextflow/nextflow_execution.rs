@@ -0,0 +1,266 @@
+//! How a CLI run was executed, recorded alongside the analysis.
+//!
+//! Outputs alone do not say how they were produced. A recipient auditing or
+//! re-running an analysis also needs the Nextflow version, the profiles and
+//! configuration files that were applied, the executor, and whether the run
+//! resumed an earlier session. This module gathers those details from the
+//! command line and the `.nextflow.log` and bundles the extra configuration
+//! files next to the outputs.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{epi4you_errors::Epi4youError, nextflow_log_parser::WorkflowSource};
+
+use super::{
+    nextflow_command::NextflowCommand,
+    nextflow_log_item::NxfLogItem,
+    nextflow_output_dir::OutputDirStrategy,
+    nextflow_reports::ReportArtefact,
+    nextflow_resources::ResourceSummary,
+    nextflow_timeline::{LogEvent, NextflowTimeline},
+    nextflow_work::WorkTaskDetails,
+};
+
+/// Folder of the staged analysis that extra `-c` configuration files are
+/// copied into.
+pub const BUNDLED_CONFIG_DIR: &str = "epi4you/config";
+/// Options that name an additional configuration file.
+const CONFIG_OPTIONS: [&str; 3] = ["-c", "-C", "-config"];
+
+/// Execution details of a CLI run.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionDetails {
+    /// Command line the run was started with.
+    pub command: String,
+    /// Where the workflow came from and whether Desktop knows it.
+    pub workflow: Option<WorkflowSource>,
+    /// Version of Nextflow that ran the workflow.
+    pub nextflow_version: Option<String>,
+    /// Configuration profiles applied, in order.
+    pub profiles: Vec<String>,
+    /// Configuration files read, including those given with `-c`.
+    pub config_files: Vec<ConfigFileDetails>,
+    /// Executors tasks were dispatched to.
+    pub executors: Vec<String>,
+    /// Nextflow session UUID, shared by a run and its resumptions.
+    pub session_id: String,
+    /// Present if the run was started with `-resume`.
+    pub resume: Option<ResumeDetails>,
+    /// Folder the run published its results into.
+    pub output_dir: Option<String>,
+    /// How [`ExecutionDetails::output_dir`] was found.
+    pub output_dir_strategy: Option<OutputDirStrategy>,
+    /// Tasks whose work directories were asked for with `--with-work`.
+    #[serde(default)]
+    pub work_tasks: Vec<WorkTaskDetails>,
+    /// Nextflow report, timeline and DAG files the run was asked to write.
+    #[serde(default)]
+    pub reports: Vec<ReportArtefact>,
+    /// CPU, memory, time and I/O used, if the run left an execution trace.
+    #[serde(default)]
+    pub resources: Option<ResourceSummary>,
+}
+
+/// One configuration file read by a run.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigFileDetails {
+    /// Path as given on the command line or reported in the log.
+    pub path: String,
+    /// Whether the file was named with `-c` rather than found by Nextflow.
+    pub user_supplied: bool,
+    /// Location of the bundled copy relative to the analysis folder.
+    pub bundled: Option<String>,
+}
+
+/// Session lineage of a resumed run.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResumeDetails {
+    /// Session or run name given to `-resume`; `None` resumes the last run.
+    pub requested: Option<String>,
+    /// Earlier runs in the launch folder that share this session, oldest first.
+    pub previous_runs: Vec<String>,
+}
+
+impl ExecutionDetails {
+    /// Gathers execution details from the command line, log and run history.
+    ///
+    /// `history` is every run known in the launch folder, in launch order.
+    pub fn from_run(
+        wf_analysis: &NxfLogItem,
+        command: &NextflowCommand,
+        timeline: &NextflowTimeline,
+        history: &[NxfLogItem],
+    ) -> Self {
+        let mut details = ExecutionDetails {
+            command: wf_analysis.command.clone(),
+            session_id: wf_analysis.session_id.clone(),
+            ..Default::default()
+        };
+
+        for profile in command
+            .option("-profile")
+            .into_iter()
+            .flat_map(|profiles| profiles.split(','))
+        {
+            push_unique(&mut details.profiles, profile.trim());
+        }
+        for option in CONFIG_OPTIONS {
+            for path in command.option_values(option) {
+                details.config_files.push(ConfigFileDetails {
+                    path: path.to_owned(),
+                    user_supplied: true,
+                    bundled: None,
+                });
+            }
+        }
+
+        for entry in &timeline.events {
+            match &entry.event {
+                LogEvent::NextflowVersion { version } => {
+                    details
+                        .nextflow_version
+                        .get_or_insert_with(|| version.clone());
+                }
+                LogEvent::Profile { profile } => push_unique(&mut details.profiles, profile),
+                LogEvent::Executor { executor } => push_unique(&mut details.executors, executor),
+                LogEvent::ConfigFile { path } => {
+                    let known = details.config_files.iter().any(|config| {
+                        Path::new(path).ends_with(config.path.trim_start_matches("./"))
+                    });
+                    if !known {
+                        details.config_files.push(ConfigFileDetails {
+                            path: path.clone(),
+                            user_supplied: false,
+                            bundled: None,
+                        });
+                    }
+                }
+                LogEvent::Session { session_id } if details.session_id.is_empty() => {
+                    details.session_id = session_id.clone();
+                }
+                _ => {}
+            }
+        }
+
+        if command.has_option("-resume") {
+            let previous_runs = history
+                .iter()
+                .take_while(|item| item.run_name != wf_analysis.run_name)
+                .filter(|item| item.session_id == details.session_id)
+                .map(|item| item.run_name.clone())
+                .collect();
+            details.resume = Some(ResumeDetails {
+                requested: command.option("-resume").map(str::to_owned),
+                previous_runs,
+            });
+        }
+        details
+    }
+
+    /// Copies the configuration files given with `-c` into the staged analysis.
+    ///
+    /// Relative paths are resolved against `launch_dir`. A file that no longer
+    /// exists is recorded without a bundled copy.
+    pub fn bundle_config_files(
+        &mut self,
+        launch_dir: &Path,
+        staging: &Path,
+    ) -> Result<(), Epi4youError> {
+        let target_dir = staging.join(BUNDLED_CONFIG_DIR);
+        for (idx, config) in self.config_files.iter_mut().enumerate() {
+            if !config.user_supplied {
+                continue;
+            }
+            let source = launch_dir.join(&config.path);
+            if !source.is_file() {
+                log::warn!("config file [{}] no longer exists", source.display());
+                continue;
+            }
+
+            let file_name = source
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            // the same name may be given twice from different folders
+            let file_name = match target_dir.join(&file_name).exists() {
+                true => format!("{idx}_{file_name}"),
+                false => file_name,
+            };
+            fs::create_dir_all(&target_dir).map_err(|err| {
+                Epi4youError::FailedToCreateFolder(target_dir.clone(), err.into())
+            })?;
+            let target = target_dir.join(&file_name);
+            fs::copy(&source, &target)
+                .map_err(|err| Epi4youError::FailedToWritePath(target.clone(), err.into()))?;
+            log::info!("bundling config file [{}]", source.display());
+            config.bundled = Some(
+                PathBuf::from(BUNDLED_CONFIG_DIR)
+                    .join(file_name)
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
+        Ok(())
+    }
+}
+
+fn push_unique(values: &mut Vec<String>, value: &str) {
+    if !value.is_empty() && !values.iter().any(|known| known == value) {
+        values.push(value.to_owned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExecutionDetails;
+    use crate::nextflow::{
+        nextflow_command::NextflowCommand, nextflow_history::parse_history,
+        nextflow_timeline::NextflowTimeline,
+    };
+
+    #[test]
+    fn gathers_profiles_configs_executor_and_lineage() {
+        let history = parse_history("\
+2026-10-18 09:00:00\t5m\tfirst_run\tERR\tabc\tsess-1\tnextflow run wf -profile standard
+2026-10-18 09:30:00\t5m\tother_run\tOK\tabc\tsess-2\tnextflow run wf
+2026-10-18 10:00:00\t1m\tclever_ampere\tOK\tabc\tsess-1\tnextflow -c extra.config run wf -profile standard,gpu -resume
+");
+        let log = "\
+Oct-18 10:00:01.000 [main] INFO  nextflow.cli.CmdRun - N E X T F L O W  ~  version 23.04.2
+Oct-18 10:00:01.500 [main] DEBUG nextflow.config.ConfigBuilder - Parsing config file: /runs/nextflow.config
+Oct-18 10:00:01.600 [main] DEBUG nextflow.config.ConfigBuilder - Parsing config file: /runs/extra.config
+Oct-18 10:00:03.800 [main] INFO  nextflow.executor.Executor - [warm up] executor > local
+";
+        let run = &history[2];
+        let details = ExecutionDetails::from_run(
+            run,
+            &NextflowCommand::parse(&run.command),
+            &NextflowTimeline::parse(log),
+            &history,
+        );
+
+        assert_eq!(details.nextflow_version.as_deref(), Some("23.04.2"));
+        assert_eq!(details.profiles, ["standard", "gpu"]);
+        assert_eq!(details.executors, ["local"]);
+        let configs: Vec<(&str, bool)> = details
+            .config_files
+            .iter()
+            .map(|config| (config.path.as_str(), config.user_supplied))
+            .collect();
+        assert_eq!(
+            configs,
+            [("extra.config", true), ("/runs/nextflow.config", false)]
+        );
+        let resume = details.resume.unwrap();
+        assert_eq!(resume.requested, None);
+        assert_eq!(resume.previous_runs, ["first_run"]);
+    }
+}
@@ -0,0 +1,105 @@
+//! Native reader for the `.nextflow/history` file.
+//!
+//! Every `nextflow run` appends one tab-separated line to this file, and
+//! `nextflow log` does little more than print it back. Reading it directly
+//! means runs can be listed and bundled on machines without Nextflow or Java.
+
+use std::{fs, path::Path};
+
+use super::nextflow_log_item::NxfLogItem;
+
+/// Location of the history file relative to the launch directory.
+pub const HISTORY_FILE: &str = ".nextflow/history";
+
+/// Reads the history file below `folder`, if there is one.
+///
+/// Returns `None` when the file is absent or unreadable so that callers can
+/// fall back to `nextflow log`.
+pub fn read_history(folder: &Path) -> Option<Vec<NxfLogItem>> {
+    let path = folder.join(HISTORY_FILE);
+    match fs::read_to_string(&path) {
+        Ok(content) => {
+            log::info!("reading run history from [{}]", path.display());
+            Some(parse_history(&content))
+        }
+        Err(err) => {
+            log::debug!("no usable history at [{}] - {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Parses the content of a history file in run order.
+///
+/// Lines are `timestamp, duration, run_name, status, revision_id, session_id,
+/// command` separated by tabs, with `-` for values not yet known (a run that
+/// is still going has no duration or status). Lines in the two-column format
+/// of very old Nextflow releases carry no run name and are skipped.
+pub fn parse_history(content: &str) -> Vec<NxfLogItem> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            let item = parse_history_line(line);
+            if item.is_none() {
+                log::debug!("skipping unrecognised history line [{line}]");
+            }
+            item
+        })
+        .collect()
+}
+
+fn parse_history_line(line: &str) -> Option<NxfLogItem> {
+    let cols: Vec<&str> = line.splitn(7, '\t').collect();
+    let [timestamp, duration, run_name, status, revision_id, session_id, command] = cols[..] else {
+        return None;
+    };
+
+    Some(NxfLogItem {
+        timestamp: timestamp.trim().to_owned(),
+        duration: duration.trim().to_owned(),
+        run_name: run_name.trim().to_owned(),
+        status: status.trim().to_owned(),
+        revision_id: revision_id.trim().to_owned(),
+        session_id: session_id.trim().to_owned(),
+        command: command.trim().to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_history;
+
+    #[test]
+    fn parses_completed_and_running_runs() {
+        let history = "\
+2026-10-18 10:00:00\t1m 2s\tclever_ampere\tOK\tabc1234567\t11111111-2222-3333-4444-555555555555\tnextflow run epi2me-labs/wf-alignment --out_dir output
+2026-10-18 11:00:00\t-\tsad_turing\t-\t-\t66666666-2222-3333-4444-555555555555\tnextflow run main.nf
+";
+        let items = parse_history(history);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].run_name, "clever_ampere");
+        assert_eq!(items[0].status, "OK");
+        assert_eq!(items[0].duration, "1m 2s");
+        assert_eq!(
+            items[0].command,
+            "nextflow run epi2me-labs/wf-alignment --out_dir output"
+        );
+        assert_eq!(items[1].status, "-");
+        assert_eq!(items[1].session_id, "66666666-2222-3333-4444-555555555555");
+    }
+
+    #[test]
+    fn keeps_tabs_inside_the_command() {
+        let items = parse_history(
+            "2026-10-18 10:00:00\t5s\tbig_curie\tERR\tabc\tuuid\tnextflow run wf --label 'a\tb'\n",
+        );
+        assert_eq!(items[0].command, "nextflow run wf --label 'a\tb'");
+    }
+
+    #[test]
+    fn skips_legacy_two_column_lines() {
+        let items = parse_history("11111111-2222-3333-4444-555555555555\tnextflow run wf\n\n");
+        assert!(items.is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::epi4you_errors::Epi4youError;
 
@@ -19,7 +19,7 @@ impl<'a> Row<'a> {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct NxfLogItem {
     pub timestamp: String,
     pub duration: String,
@@ -31,6 +31,16 @@ pub struct NxfLogItem {
 }
 
 impl NxfLogItem {
+    /// Returns true if Nextflow recorded the run as successful.
+    pub fn is_ok(&self) -> bool {
+        self.status.trim() == "OK"
+    }
+
+    /// Returns the year the run started in, from its history timestamp.
+    pub fn start_year(&self) -> Option<i32> {
+        self.timestamp.trim().get(..4)?.parse().ok()
+    }
+
     pub fn init(row: Row) -> Result<NxfLogItem, Epi4youError> {
         return Ok(NxfLogItem {
             timestamp: row.timestamp.into(),
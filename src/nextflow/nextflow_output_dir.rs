@@ -0,0 +1,287 @@
+//! Resolution of the folder a CLI run published its results into.
+//!
+//! EPI2ME workflows take `--out_dir`, nf-core style workflows `--outdir`, and
+//! either may come from the command line, a `-params-file` or a default in the
+//! workflow itself. No single source is reliable, so several are tried in turn
+//! and the one that worked is reported, so that a surprising choice can be
+//! corrected with `--out-dir`.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use walkdir::WalkDir;
+
+use super::{
+    nextflow_command::NextflowCommand,
+    nextflow_params,
+    nextflow_timeline::{LogEvent, NextflowTimeline},
+    nextflow_trace,
+};
+
+/// Parameter names workflows use for their output folder.
+const OUTPUT_DIR_PARAMS: [&str; 5] = ["out_dir", "out-dir", "outdir", "outDir", "output_dir"];
+/// Folder EPI2ME workflows publish into when no output folder is given.
+const DEFAULT_OUTPUT_DIR: &str = "output";
+/// Folders trace files are commonly written to below the output folder.
+const TRACE_SUBFOLDERS: [&str; 2] = ["execution", "pipeline_info"];
+/// Folders of the launch directory that never hold published results.
+const SKIPPED_FOLDERS: [&str; 2] = ["work", ".nextflow"];
+
+/// How the output folder of a run was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputDirStrategy {
+    /// Given with `--out-dir` on `nextflow-run`.
+    Override,
+    /// A `--out_dir`-style parameter on the command line.
+    CommandLine,
+    /// An output parameter in the `-params-file`.
+    ParamsFile,
+    /// An output parameter in the summary the workflow logged.
+    LogParameters,
+    /// The common folder of the files the log says were published.
+    PublishedFiles,
+    /// The conventional `output/` folder of the launch directory.
+    DefaultFolder,
+    /// The folder holding the newest execution trace.
+    Trace,
+}
+
+impl fmt::Display for OutputDirStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            OutputDirStrategy::Override => "--out-dir override",
+            OutputDirStrategy::CommandLine => "command-line parameter",
+            OutputDirStrategy::ParamsFile => "params file",
+            OutputDirStrategy::LogParameters => "parameters in the log",
+            OutputDirStrategy::PublishedFiles => "published files in the log",
+            OutputDirStrategy::Trace => "execution trace location",
+            OutputDirStrategy::DefaultFolder => "default output folder",
+        };
+        f.write_str(text)
+    }
+}
+
+/// Finds the output folder of a run launched from `launch_dir`.
+///
+/// Sources are tried in order of reliability: the override, the command
+/// line, the params file, the parameter summary in the log, published-file
+/// log lines, `output/` and finally the location of the newest execution
+/// trace. The trace comes last because any run publishing next to this one
+/// may have written it. A candidate is only accepted if it is an existing
+/// folder. Paths from the run are relative to `launch_dir`; the override, like
+/// any other path the caller gives, is relative to the current directory.
+pub fn resolve_output_dir(
+    command: &NextflowCommand,
+    nf_log: &str,
+    launch_dir: &Path,
+    out_dir: Option<&Path>,
+) -> Option<(PathBuf, OutputDirStrategy)> {
+    if let Some(out_dir) = out_dir {
+        return std::path::absolute(out_dir)
+            .ok()
+            .filter(|dir| dir.is_dir())
+            .map(|dir| (dir, OutputDirStrategy::Override));
+    }
+
+    let command_dir = parse_output_dir(command).map(PathBuf::from);
+    let params_file_dir = command
+        .option("-params-file")
+        .map(|path| nextflow_params::read_params_file(&launch_dir.join(path)))
+        .and_then(|params| output_param(&params));
+    let log_dir = output_param(&nextflow_params::params_from_log(nf_log));
+
+    let candidates = [
+        (command_dir, OutputDirStrategy::CommandLine),
+        (params_file_dir, OutputDirStrategy::ParamsFile),
+        (log_dir, OutputDirStrategy::LogParameters),
+        (published_dir(nf_log), OutputDirStrategy::PublishedFiles),
+    ];
+    for (candidate, strategy) in candidates {
+        if let Some(dir) = candidate.and_then(|dir| existing_dir(launch_dir, &dir)) {
+            return Some((dir, strategy));
+        }
+    }
+
+    existing_dir(launch_dir, Path::new(DEFAULT_OUTPUT_DIR))
+        .map(|dir| (dir, OutputDirStrategy::DefaultFolder))
+        .or_else(|| trace_dir(launch_dir).map(|dir| (dir, OutputDirStrategy::Trace)))
+}
+
+/// Returns the output-folder parameter given on the command line.
+///
+/// Quoting is honoured, so paths containing spaces survive.
+fn parse_output_dir(command: &NextflowCommand) -> Option<&str> {
+    OUTPUT_DIR_PARAMS
+        .iter()
+        .find_map(|key| command.param(key))
+        .filter(|value| !value.is_empty() && *value != "true")
+}
+
+/// Resolves `dir` against the launch directory and keeps it if it exists.
+///
+/// Absolute paths are used as they are, so results published outside the
+/// launch directory are still found.
+fn existing_dir(launch_dir: &Path, dir: &Path) -> Option<PathBuf> {
+    let candidate = launch_dir.join(dir);
+    candidate.is_dir().then_some(candidate)
+}
+
+fn output_param(params: &Map<String, Value>) -> Option<PathBuf> {
+    OUTPUT_DIR_PARAMS
+        .iter()
+        .find_map(|key| params.get(*key))
+        .and_then(Value::as_str)
+        .map(PathBuf::from)
+}
+
+/// Returns the deepest folder shared by every file the log says was published.
+fn published_dir(nf_log: &str) -> Option<PathBuf> {
+    let timeline = NextflowTimeline::parse(nf_log);
+    let mut targets = timeline
+        .events
+        .iter()
+        .filter_map(|entry| match &entry.event {
+            LogEvent::FilePublished { target, .. } => Some(Path::new(target)),
+            _ => None,
+        });
+
+    let mut common = targets.next()?.parent()?.to_path_buf();
+    for target in targets {
+        while !target.starts_with(&common) {
+            common = common.parent()?.to_path_buf();
+        }
+    }
+    (common.components().count() > 1).then_some(common)
+}
+
+/// Returns the output folder implied by the newest trace below `launch_dir`.
+///
+/// A trace in `execution/` or `pipeline_info/` belongs to the folder above.
+/// A trace directly in the launch directory says nothing about the outputs.
+fn trace_dir(launch_dir: &Path) -> Option<PathBuf> {
+    let trace = WalkDir::new(launch_dir)
+        .min_depth(1)
+        .max_depth(1)
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            !SKIPPED_FOLDERS.contains(&name.as_ref())
+        })
+        .flatten()
+        .filter(|entry| entry.file_type().is_dir())
+        .filter_map(|entry| nextflow_trace::find_trace(entry.path()))
+        .max_by_key(|path| path.metadata().and_then(|meta| meta.modified()).ok())?;
+
+    let parent = trace.parent()?;
+    let is_subfolder = parent
+        .file_name()
+        .is_some_and(|name| TRACE_SUBFOLDERS.contains(&name.to_string_lossy().as_ref()));
+    let dir = if is_subfolder {
+        parent.parent()?
+    } else {
+        parent
+    };
+    (dir != launch_dir).then(|| dir.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{parse_output_dir, published_dir, resolve_output_dir, OutputDirStrategy};
+    use crate::{nextflow::nextflow_command::NextflowCommand, tempdir::unique_test_dir};
+
+    fn output_dir_of(command: &str) -> Option<String> {
+        parse_output_dir(&NextflowCommand::parse(command)).map(str::to_owned)
+    }
+
+    #[test]
+    fn parses_output_dir_argument_with_equals() {
+        assert_eq!(
+            output_dir_of("nextflow run wf --out_dir=results").as_deref(),
+            Some("results")
+        );
+        assert_eq!(
+            output_dir_of("nextflow run wf --out-dir=results").as_deref(),
+            Some("results")
+        );
+    }
+
+    #[test]
+    fn parses_output_dir_argument_with_separate_value() {
+        assert_eq!(
+            output_dir_of("nextflow run wf --out_dir results -profile test").as_deref(),
+            Some("results")
+        );
+        assert_eq!(
+            output_dir_of("nextflow run wf --out-dir 'results' -profile test").as_deref(),
+            Some("results")
+        );
+        assert_eq!(
+            output_dir_of("nextflow run wf --outdir \"/data/my results\"").as_deref(),
+            Some("/data/my results")
+        );
+    }
+
+    #[test]
+    fn prefers_command_line_then_params_file() {
+        let tmp = unique_test_dir("out-dir");
+        let root = tmp.path.clone();
+        fs::create_dir_all(root.join("my results")).unwrap();
+        fs::create_dir_all(root.join("from_file")).unwrap();
+        fs::create_dir_all(root.join("output")).unwrap();
+        fs::write(root.join("params.json"), r#"{"outdir": "from_file"}"#).unwrap();
+
+        let command = NextflowCommand::parse("nextflow run wf --out_dir 'my results'");
+        assert_eq!(
+            resolve_output_dir(&command, "", &root, None),
+            Some((root.join("my results"), OutputDirStrategy::CommandLine))
+        );
+
+        let command = NextflowCommand::parse("nextflow run wf -params-file params.json");
+        assert_eq!(
+            resolve_output_dir(&command, "", &root, None),
+            Some((root.join("from_file"), OutputDirStrategy::ParamsFile))
+        );
+
+        let command = NextflowCommand::parse("nextflow run wf --outdir missing");
+        assert_eq!(
+            resolve_output_dir(&command, "", &root, None),
+            Some((root.join("output"), OutputDirStrategy::DefaultFolder))
+        );
+    }
+
+    #[test]
+    fn prefers_default_folder_to_another_runs_trace() {
+        let tmp = unique_test_dir("out-dir");
+        let root = tmp.path.clone();
+        fs::create_dir_all(root.join("results/execution")).unwrap();
+        fs::write(root.join("results/execution/trace.txt"), "name\tstatus\n").unwrap();
+
+        let command = NextflowCommand::parse("nextflow run wf");
+        assert_eq!(
+            resolve_output_dir(&command, "", &root, None),
+            Some((root.join("results"), OutputDirStrategy::Trace))
+        );
+        fs::create_dir_all(root.join("output")).unwrap();
+        assert_eq!(
+            resolve_output_dir(&command, "", &root, None),
+            Some((root.join("output"), OutputDirStrategy::DefaultFolder))
+        );
+    }
+
+    #[test]
+    fn finds_common_folder_of_published_files() {
+        let log = "\
+Oct-18 10:00:30.000 [Actor Thread 3] TRACE nextflow.processor.PublishDir - publishing file: /runs/work/ab/cdef/a.bam -[copy]-> /data/results/bams/a.bam
+Oct-18 10:00:31.000 [Actor Thread 3] TRACE nextflow.processor.PublishDir - publishing file: /runs/work/cd/ef01/report.html -[copy]-> /data/results/report.html
+";
+        assert_eq!(published_dir(log), Some(PathBuf::from("/data/results")));
+        assert_eq!(published_dir(""), None);
+    }
+}
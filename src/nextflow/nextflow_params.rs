@@ -0,0 +1,165 @@
+//! Recovery of the workflow parameters a CLI run was launched with.
+//!
+//! Desktop launches every analysis with a `params.json` and keeps it, along
+//! with a `launch.json`, in the instance folder so the GUI can show the
+//! inputs of a run. A CLI run has neither, so the parameters are pieced back
+//! together from three places, in increasing order of precedence: the
+//! parameter summary EPI2ME workflows print into the log, the `-params-file`
+//! and the `--` options on the command line.
+
+use std::{fs, path::Path};
+
+use serde_json::{Map, Value};
+
+use super::nextflow_command::{typed_value, NextflowCommand};
+
+/// Marker of the first task; the parameter summary is printed before it.
+const FIRST_TASK_MARKER: &str = "process > ";
+/// Heading of the summary section that lists workflow metadata, not params.
+const CORE_OPTIONS_HEADING: &str = "Core Nextflow options";
+
+/// Collects the parameters of a run from its log, params file and command.
+///
+/// `launch_dir` is the folder relative paths in the command refer to.
+pub fn recover_params(
+    command: &NextflowCommand,
+    nf_log: &str,
+    launch_dir: &Path,
+) -> Map<String, Value> {
+    let mut params = params_from_log(nf_log);
+    if let Some(params_file) = command.option("-params-file") {
+        params.extend(read_params_file(&launch_dir.join(params_file)));
+    }
+    params.extend(command.params_json());
+    params
+}
+
+/// Reads a JSON `-params-file`.
+///
+/// YAML params files are not understood and are skipped with a warning.
+pub fn read_params_file(path: &Path) -> Map<String, Value> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) => {
+            log::warn!("unable to read params file [{}] - {err}", path.display());
+            return Map::new();
+        }
+    };
+    match serde_json::from_str::<Value>(&content) {
+        Ok(Value::Object(params)) => {
+            log::info!("read parameters from [{}]", path.display());
+            params
+        }
+        _ => {
+            log::warn!(
+                "params file [{}] is not a JSON object - skipping",
+                path.display()
+            );
+            Map::new()
+        }
+    }
+}
+
+/// Parses the `name : value` parameter summary EPI2ME workflows log at start.
+///
+/// Entries under the `Core Nextflow options` heading describe the run rather
+/// than its parameters and are skipped, as are ANSI colour codes. Only the
+/// part of the log before the first task is read.
+pub fn params_from_log(nf_log: &str) -> Map<String, Value> {
+    let mut params = Map::new();
+    let mut core_section = false;
+
+    for line in nf_log.lines() {
+        let line = strip_ansi(line);
+        // summary lines follow the logger prefix on the first line only
+        let text = line
+            .split_once(" - ")
+            .map_or(line.as_str(), |(_, text)| text);
+        let text = text.trim();
+        if text.contains(FIRST_TASK_MARKER) {
+            break;
+        }
+
+        match text.split_once(" : ") {
+            Some((key, value)) if !core_section && is_param_name(key.trim()) => {
+                params.insert(key.trim().to_owned(), typed_value(value.trim()));
+            }
+            Some(_) => {}
+            None if !text.is_empty() && !text.contains('[') => {
+                core_section = text.contains(CORE_OPTIONS_HEADING);
+            }
+            None => {}
+        }
+    }
+    params
+}
+
+fn is_param_name(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Removes `ESC [ ... letter` colour sequences.
+fn strip_ansi(line: &str) -> String {
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use serde_json::json;
+
+    use super::{params_from_log, recover_params};
+    use crate::{nextflow::nextflow_command::NextflowCommand, tempdir::unique_test_dir};
+
+    const LOG: &str = "\
+Oct-18 10:00:03.500 [main] INFO  nextflow.Nextflow - \u{1b}[1mCore Nextflow options\u{1b}[0m
+  \u{1b}[0;34mrunName        : \u{1b}[0;32mclever_ampere\u{1b}[0m
+  \u{1b}[0;34mprofile        : \u{1b}[0;32mstandard\u{1b}[0m
+
+\u{1b}[1mInput Options\u{1b}[0m
+  \u{1b}[0;34mfastq          : \u{1b}[0;32m/data/reads\u{1b}[0m
+  \u{1b}[0;34mthreads        : \u{1b}[0;32m4\u{1b}[0m
+Oct-18 10:00:04.000 [Task submitter] INFO  nextflow.Session - [ab/cdef12] Submitted process > align (1)
+";
+
+    #[test]
+    fn reads_the_logged_parameter_summary() {
+        let params = params_from_log(LOG);
+        assert_eq!(
+            serde_json::Value::Object(params),
+            json!({"fastq": "/data/reads", "threads": 4})
+        );
+    }
+
+    #[test]
+    fn command_overrides_params_file_and_log() {
+        let tmp = unique_test_dir("params");
+        let dir = tmp.path.clone();
+        fs::write(dir.join("params.json"), r#"{"threads": 8, "sample": "s1"}"#).unwrap();
+
+        let command =
+            NextflowCommand::parse("nextflow run wf -params-file params.json --sample s2");
+        let params = recover_params(&command, LOG, Path::new(&dir));
+        assert_eq!(
+            serde_json::Value::Object(params),
+            json!({"fastq": "/data/reads", "threads": 8, "sample": "s2"})
+        );
+    }
+}
@@ -1,20 +1,35 @@
-use std::collections::HashMap;
-
 use serde::{ser::SerializeMap, Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ProgressItem {
     pub status: String,
     pub tag: String,
-    pub total: u16,
-    pub complete: u16,
+    pub total: u32,
+    pub complete: u32,
+    #[serde(default)]
+    pub failed: u32,
+    #[serde(default)]
+    pub cached: u32,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ProgressJson {
     // #[serde(rename = ulid_str)]
     pub name: String,
-    pub key: HashMap<String, ProgressItem>,
+    /// Processes in the order the run first ran them, as Desktop lists them.
+    pub key: Vec<(String, ProgressItem)>,
+}
+
+/// Serializes process entries as a map without losing their order.
+struct OrderedItems<'a>(&'a [(String, ProgressItem)]);
+
+impl Serialize for OrderedItems<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_map(self.0.iter().map(|(key, item)| (key, item)))
+    }
 }
 
 impl Serialize for ProgressJson {
@@ -24,7 +39,7 @@ impl Serialize for ProgressJson {
     {
         let item_name = self.name.to_owned();
         let mut struct_ser = serializer.serialize_map(Some(1))?;
-        struct_ser.serialize_entry(&item_name, &self.key)?;
+        struct_ser.serialize_entry(&item_name, &OrderedItems(&self.key))?;
         struct_ser.end()
     }
 }
@@ -0,0 +1,304 @@
+//! Nextflow's own execution report, timeline and DAG of a run.
+//!
+//! `-with-report`, `-with-timeline` and `-with-dag` write HTML (or, for the
+//! DAG, also `.dot`, `.mmd` and image) files into the launch folder unless
+//! given a path, so they usually sit outside the published output folder and
+//! would be left behind. They are found from the command line and the
+//! `.nextflow.log`, which also catches those enabled in `nextflow.config`,
+//! copied into a reserved folder of the staged analysis and listed in the
+//! manifest next to the outputs.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde::{Deserialize, Serialize};
+
+use crate::epi4you_errors::Epi4youError;
+
+use super::nextflow_command::NextflowCommand;
+
+/// Folder of the staged analysis that report artefacts are copied into.
+pub const BUNDLED_REPORTS_DIR: &str = "epi4you/reports";
+/// Loggers of the observers that write the artefacts.
+const OBSERVER_LOGGERS: [&str; 2] = [" nextflow.trace.", " nextflow.dag."];
+/// Extensions Nextflow can render a DAG as.
+const DAG_EXTENSIONS: [&str; 7] = ["html", "dot", "mmd", "svg", "png", "pdf", "gexf"];
+
+/// Which Nextflow artefact a file is.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportKind {
+    /// Execution report from `-with-report`.
+    Report,
+    /// Task timeline from `-with-timeline`.
+    Timeline,
+    /// Workflow graph from `-with-dag`.
+    Dag,
+}
+
+impl ReportKind {
+    const ALL: [ReportKind; 3] = [ReportKind::Report, ReportKind::Timeline, ReportKind::Dag];
+
+    /// Command-line option that asks for the artefact.
+    fn option(self) -> &'static str {
+        match self {
+            ReportKind::Report => "-with-report",
+            ReportKind::Timeline => "-with-timeline",
+            ReportKind::Dag => "-with-dag",
+        }
+    }
+
+    /// Class of the observer that renders the artefact.
+    fn observer(self) -> &'static str {
+        match self {
+            ReportKind::Report => "ReportObserver",
+            ReportKind::Timeline => "TimelineObserver",
+            ReportKind::Dag => "GraphObserver",
+        }
+    }
+
+    /// Returns true if `file_name` is one Nextflow writes by default, such as
+    /// `report.html` or `report-20261018-1234abcd.html`.
+    fn is_default_name(self, file_name: &str) -> bool {
+        let prefix = match self {
+            ReportKind::Report => "report",
+            ReportKind::Timeline => "timeline",
+            ReportKind::Dag => "dag",
+        };
+        let Some((stem, _)) = file_name.rsplit_once('.') else {
+            return false;
+        };
+        self.has_extension(file_name) && (stem == prefix || stem.starts_with(&format!("{prefix}-")))
+    }
+
+    /// Returns true if `file_name` ends in an extension the artefact can have.
+    fn has_extension(self, file_name: &str) -> bool {
+        let Some((_, extension)) = file_name.rsplit_once('.') else {
+            return false;
+        };
+        match self {
+            ReportKind::Dag => DAG_EXTENSIONS.contains(&extension),
+            _ => extension == "html",
+        }
+    }
+
+    /// Returns true if `token`, taken from `line` of the log, names the
+    /// artefact: a default file name, or any file of the right type named by
+    /// the artefact's own observer.
+    fn is_mentioned_by(self, line: &str, token: &str) -> bool {
+        let Some(file_name) = Path::new(token).file_name() else {
+            return false;
+        };
+        let file_name = file_name.to_string_lossy();
+        self.is_default_name(&file_name)
+            || (line.contains(self.observer()) && self.has_extension(&file_name))
+    }
+}
+
+/// A report artefact of a run.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportArtefact {
+    /// Which artefact this is.
+    pub kind: ReportKind,
+    /// Where the run wrote it.
+    pub path: String,
+    /// Location in the analysis folder; `None` if the file no longer exists.
+    pub bundled: Option<String>,
+}
+
+/// Finds the report artefacts a run asked for.
+///
+/// A path given on the command line wins, then a matching path mentioned by
+/// the observer that rendered it in the log. A log mention is enough on its
+/// own, so artefacts enabled in `nextflow.config` are found too. An option
+/// given without a path falls back to Nextflow's default name in the launch
+/// folder, taking the first such file written after the run `started` so that
+/// later runs in a shared folder are not confused with it. Relative paths are
+/// resolved against `launch_dir`.
+pub fn find_reports(
+    command: &NextflowCommand,
+    nf_log: &str,
+    launch_dir: &Path,
+    started: &str,
+) -> Vec<(ReportKind, PathBuf)> {
+    let mentioned: Vec<(&str, &str)> = nf_log
+        .lines()
+        .filter(|line| OBSERVER_LOGGERS.iter().any(|logger| line.contains(logger)))
+        .flat_map(|line| line.split_whitespace().map(move |token| (line, token)))
+        .map(|(line, token)| {
+            let token = token.trim_matches(|c| matches!(c, '\'' | '"' | '`' | '[' | ']' | ','));
+            (line, token)
+        })
+        .collect();
+
+    let mut found = Vec::new();
+    for kind in ReportKind::ALL {
+        let requested = command.has_option(kind.option());
+        let path = command
+            .option(kind.option())
+            .or_else(|| {
+                mentioned
+                    .iter()
+                    .find(|(line, token)| kind.is_mentioned_by(line, token))
+                    .map(|(_, token)| *token)
+            })
+            .map(|path| launch_dir.join(path))
+            .or_else(|| {
+                requested
+                    .then(|| default_file(kind, launch_dir, started))
+                    .flatten()
+            });
+        match path {
+            Some(path) => found.push((kind, path)),
+            None if requested => {
+                log::warn!("{} was given but its file was not found", kind.option())
+            }
+            None => {}
+        }
+    }
+    found
+}
+
+/// Copies the artefacts into the staged analysis and records them.
+///
+/// Files already inside the run's `output_dir` are bundled with the outputs
+/// and only recorded. A file that has since been removed is recorded without
+/// a copy.
+pub fn bundle_reports(
+    found: &[(ReportKind, PathBuf)],
+    output_dir: &Path,
+    staging: &Path,
+) -> Result<Vec<ReportArtefact>, Epi4youError> {
+    let mut artefacts = Vec::new();
+    for (kind, path) in found {
+        let bundled = if !path.is_file() {
+            log::warn!("{} file [{}] not found", kind.option(), path.display());
+            None
+        } else if let Ok(relative) = path.strip_prefix(output_dir) {
+            Some(PathBuf::from("output").join(relative))
+        } else {
+            let bundled =
+                PathBuf::from(BUNDLED_REPORTS_DIR).join(path.file_name().unwrap_or_default());
+            let target = staging.join(&bundled);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(|err| {
+                    Epi4youError::FailedToCreateFolder(parent.to_path_buf(), err.into())
+                })?;
+            }
+            log::info!("bundling {} [{}]", kind.option(), path.display());
+            fs::copy(path, &target)
+                .map_err(|err| Epi4youError::FailedToWritePath(target.clone(), err.into()))?;
+            Some(bundled)
+        };
+        artefacts.push(ReportArtefact {
+            kind: *kind,
+            path: path.to_string_lossy().into_owned(),
+            bundled: bundled.map(|bundled| bundled.to_string_lossy().into_owned()),
+        });
+    }
+    Ok(artefacts)
+}
+
+/// Picks the default-named file written for a run started at `started`.
+fn default_file(kind: ReportKind, launch_dir: &Path, started: &str) -> Option<PathBuf> {
+    let started = NaiveDateTime::parse_from_str(started.trim(), "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .and_then(|time| Local.from_local_datetime(&time).earliest())
+        .map(SystemTime::from);
+    fs::read_dir(launch_dir)
+        .ok()?
+        .flatten()
+        .filter(|entry| kind.is_default_name(&entry.file_name().to_string_lossy()))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .filter(|(modified, _)| started.is_none_or(|started| *modified >= started))
+        .min_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{bundle_reports, find_reports, ReportKind};
+    use crate::{nextflow::nextflow_command::NextflowCommand, tempdir::unique_test_dir};
+
+    #[test]
+    fn recognises_default_names() {
+        assert!(ReportKind::Report.is_default_name("report.html"));
+        assert!(ReportKind::Report.is_default_name("report-20261018-1a2b3c4d.html"));
+        assert!(!ReportKind::Report.is_default_name("reporting.html"));
+        assert!(ReportKind::Dag.is_default_name("dag.dot"));
+        assert!(!ReportKind::Timeline.is_default_name("timeline.txt"));
+    }
+
+    #[test]
+    fn finds_and_bundles_artefacts() {
+        let tmp = unique_test_dir("reports");
+        let root = tmp.path.clone();
+        fs::create_dir_all(root.join("output/execution")).unwrap();
+        fs::write(root.join("report-20261018-1a2b3c4d.html"), "report").unwrap();
+        fs::write(root.join("output/execution/timeline.html"), "timeline").unwrap();
+
+        let command = NextflowCommand::parse(
+            "nextflow run wf -with-report -with-timeline output/execution/timeline.html -with-dag",
+        );
+        let found = find_reports(&command, "", &root, "2026-10-18 10:00:00");
+        let kinds: Vec<ReportKind> = found.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(kinds, [ReportKind::Report, ReportKind::Timeline]);
+
+        let staging = root.join("staging");
+        let artefacts = bundle_reports(&found, &root.join("output"), &staging).unwrap();
+        assert_eq!(
+            artefacts[0].bundled.as_deref(),
+            Some("epi4you/reports/report-20261018-1a2b3c4d.html")
+        );
+        assert!(staging
+            .join("epi4you/reports/report-20261018-1a2b3c4d.html")
+            .is_file());
+        assert_eq!(
+            artefacts[1].bundled.as_deref(),
+            Some("output/execution/timeline.html")
+        );
+    }
+
+    #[test]
+    fn takes_paths_the_log_mentions() {
+        let command = NextflowCommand::parse("nextflow run wf -with-dag");
+        let log = "Oct-18 10:01:01.000 [main] DEBUG nextflow.processor.PublishDir - Publishing file dag.dot
+Oct-18 10:01:02.000 [main] DEBUG nextflow.trace.GraphObserver - Rendering DAG to `/runs/graphs/dag-20261018.mmd`";
+        let found = find_reports(&command, log, std::path::Path::new("/runs"), "");
+        assert_eq!(
+            found,
+            [(
+                ReportKind::Dag,
+                std::path::PathBuf::from("/runs/graphs/dag-20261018.mmd")
+            )]
+        );
+    }
+
+    #[test]
+    fn finds_artefacts_enabled_in_the_config() {
+        let command = NextflowCommand::parse("nextflow run wf");
+        let log = "Oct-18 10:05:01.000 [main] DEBUG nextflow.trace.ReportObserver - Rendering execution report to `results/info/execution_report.html`
+Oct-18 10:05:01.500 [main] DEBUG nextflow.trace.TimelineObserver - Rendering timeline to `/runs/timeline.html`";
+        let found = find_reports(&command, log, std::path::Path::new("/runs"), "");
+        assert_eq!(
+            found,
+            [
+                (
+                    ReportKind::Report,
+                    std::path::PathBuf::from("/runs/results/info/execution_report.html")
+                ),
+                (
+                    ReportKind::Timeline,
+                    std::path::PathBuf::from("/runs/timeline.html")
+                ),
+            ]
+        );
+    }
+}
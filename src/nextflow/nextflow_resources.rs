@@ -0,0 +1,170 @@
+//! Resource usage of a run, summarised from its execution trace.
+//!
+//! Someone receiving an analysis often wants to know what reproducing it would
+//! take before they try: how many CPU hours it burnt, how much memory its
+//! largest task needed, how long it ran and how much data it moved. The trace
+//! records this per task attempt; this module adds it up per run and names the
+//! processes that account for most of it.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::nextflow_trace::TraceRecord;
+
+/// How many processes [`ResourceSummary::heaviest_processes`] lists.
+const HEAVIEST_PROCESSES: usize = 5;
+
+/// Resource usage of one run.
+///
+/// Every task attempt in the trace counts, including failed attempts and
+/// tasks cached from an earlier run, so the totals describe what running the
+/// analysis from scratch cost rather than what the last resume added.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceSummary {
+    /// Task attempts in the trace.
+    pub tasks: usize,
+    /// CPU time of all attempts, in core hours to three decimal places.
+    pub cpu_hours: f64,
+    /// Largest resident memory of any task, in bytes.
+    pub peak_rss_bytes: Option<u64>,
+    /// Largest virtual memory of any task, in bytes.
+    pub peak_vmem_bytes: Option<u64>,
+    /// Seconds from the first submission to the last task finishing, leaving
+    /// out tasks cached from an earlier run.
+    pub wall_time_seconds: Option<u64>,
+    /// Bytes read by all attempts.
+    pub read_bytes: u64,
+    /// Bytes written by all attempts.
+    pub written_bytes: u64,
+    /// Processes using the most CPU time, heaviest first.
+    pub heaviest_processes: Vec<ProcessUsage>,
+}
+
+/// Resource usage of the tasks of one process.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessUsage {
+    /// Process name.
+    pub process: String,
+    /// Task attempts of the process.
+    pub tasks: usize,
+    /// CPU time of those attempts, in core hours to three decimal places.
+    pub cpu_hours: f64,
+    /// Largest resident memory of any of them, in bytes.
+    pub peak_rss_bytes: Option<u64>,
+}
+
+/// Adds up the resource columns of a trace; `None` for an empty trace.
+///
+/// CPU time is the task's `realtime` scaled by its `%cpu`, so a task that kept
+/// four cores busy for an hour counts as four core hours.
+pub fn summarise(records: &[TraceRecord]) -> Option<ResourceSummary> {
+    if records.is_empty() {
+        return None;
+    }
+
+    let mut summary = ResourceSummary {
+        tasks: records.len(),
+        ..Default::default()
+    };
+    let mut processes: Vec<ProcessUsage> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for record in records {
+        let cpu_hours = cpu_hours(record);
+        summary.cpu_hours += cpu_hours;
+        summary.peak_rss_bytes = summary.peak_rss_bytes.max(record.peak_rss);
+        summary.peak_vmem_bytes = summary.peak_vmem_bytes.max(record.peak_vmem);
+        summary.read_bytes += record.rchar.unwrap_or_default();
+        summary.written_bytes += record.wchar.unwrap_or_default();
+
+        let idx = *index.entry(&record.process).or_insert_with(|| {
+            processes.push(ProcessUsage {
+                process: record.process.clone(),
+                ..Default::default()
+            });
+            processes.len() - 1
+        });
+        let usage = &mut processes[idx];
+        usage.tasks += 1;
+        usage.cpu_hours += cpu_hours;
+        usage.peak_rss_bytes = usage.peak_rss_bytes.max(record.peak_rss);
+    }
+
+    // cached tasks carry the timings of the run they ran in
+    let ran = || records.iter().filter(|record| record.status != "CACHED");
+    let start = ran().filter_map(|record| record.submit).min();
+    let end = ran()
+        .filter_map(|record| Some(record.submit? + record.duration? as i64))
+        .max();
+    summary.wall_time_seconds = start
+        .zip(end)
+        .map(|(start, end)| (end - start).max(0) as u64 / 1_000);
+
+    processes.sort_by(|a, b| b.cpu_hours.total_cmp(&a.cpu_hours));
+    processes.truncate(HEAVIEST_PROCESSES);
+    // short decimals survive the JSON round trip the manifest signature needs
+    summary.cpu_hours = round_hours(summary.cpu_hours);
+    for usage in processes.iter_mut() {
+        usage.cpu_hours = round_hours(usage.cpu_hours);
+    }
+    summary.heaviest_processes = processes;
+    Some(summary)
+}
+
+fn round_hours(hours: f64) -> f64 {
+    (hours * 1_000.0).round() / 1_000.0
+}
+
+/// Core hours used by one task attempt.
+fn cpu_hours(record: &TraceRecord) -> f64 {
+    match (record.realtime, record.cpu_percent) {
+        (Some(realtime), Some(percent)) => realtime as f64 / 3_600_000.0 * percent / 100.0,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::summarise;
+    use crate::nextflow::nextflow_trace::parse_trace;
+
+    const TRACE: &str = "\
+task_id\thash\tname\tstatus\tsubmit\tduration\trealtime\t%cpu\tpeak_rss\tpeak_vmem\trchar\twchar
+1\tab/cdef12\talignment:minimap2 (1)\tCOMPLETED\t2026-10-18 10:00:00.000\t1h\t1h\t400.0%\t1.5 GB\t2 GB\t100 MB\t50 MB
+2\tab/cdef13\talignment:minimap2 (2)\tFAILED\t2026-10-18 10:00:00.000\t30m\t30m\t200.0%\t900 MB\t1 GB\t10 MB\t0
+3\tcd/ef0123\tmakeReport\tCACHED\t2026-10-17 09:00:00.000\t30m\t30m\t100.0%\t200 MB\t500 MB\t5 MB\t1 MB
+";
+
+    #[test]
+    fn adds_up_every_attempt() {
+        let summary = summarise(&parse_trace(TRACE)).unwrap();
+        assert_eq!(summary.tasks, 3);
+        assert_eq!(summary.cpu_hours, 5.5);
+        assert_eq!(summary.peak_rss_bytes, Some(1_610_612_736));
+        assert_eq!(summary.peak_vmem_bytes, Some(2 << 30));
+        assert_eq!(summary.wall_time_seconds, Some(3_600));
+        assert_eq!(summary.read_bytes, 115 << 20);
+        assert_eq!(summary.written_bytes, 51 << 20);
+
+        let heaviest: Vec<(&str, usize, f64)> = summary
+            .heaviest_processes
+            .iter()
+            .map(|usage| (usage.process.as_str(), usage.tasks, usage.cpu_hours))
+            .collect();
+        assert_eq!(
+            heaviest,
+            [("alignment:minimap2", 2, 5.0), ("makeReport", 1, 0.5)]
+        );
+    }
+
+    #[test]
+    fn tolerates_traces_without_resource_columns() {
+        let summary = summarise(&parse_trace("name\tstatus\nmakeReport\tCOMPLETED\n")).unwrap();
+        assert_eq!(summary.cpu_hours, 0.0);
+        assert_eq!(summary.peak_rss_bytes, None);
+        assert_eq!(summary.wall_time_seconds, None);
+        assert!(summarise(&[]).is_none());
+    }
+}
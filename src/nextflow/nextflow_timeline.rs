@@ -0,0 +1,598 @@
+//! Structured reading of `.nextflow.log` files.
+//!
+//! The log is written for people debugging Nextflow, not for machines, but it
+//! is the only record of a run that survives on every system. This module turns
+//! it into a sequence of typed events (launch, configuration, task lifecycle,
+//! warnings, errors and completion) that the rest of `epi4you` can query
+//! instead of searching for substrings.
+
+use chrono::{Datelike, Duration, NaiveDateTime};
+use serde::Serialize;
+
+use crate::nextflow_log_parser::{parse_launch_line, parse_version, VERSION_MARKER};
+
+/// Prefix of the line recording the command that started the run.
+const COMMAND_PREFIX: &str = "$> ";
+/// Prefix of Nextflow's banner line carrying its own version.
+const NEXTFLOW_VERSION_PREFIX: &str = "N E X T F L O W  ~  version ";
+/// Prefix of the line recording the session UUID.
+const SESSION_PREFIX: &str = "Session UUID: ";
+/// Prefix of each configuration file Nextflow reads.
+const CONFIG_FILE_PREFIX: &str = "Parsing config file: ";
+/// Prefix of each `-profile` that is applied.
+const PROFILE_PREFIX: &str = "Applying config profile: ";
+/// Marker preceding the executor name.
+const EXECUTOR_MARKER: &str = "executor > ";
+/// Marker of a task being submitted.
+const SUBMITTED_MARKER: &str = "Submitted process > ";
+/// Marker of a task whose result was reused from an earlier run.
+const CACHED_MARKER: &str = "Cached process > ";
+/// Marker of a task handler finishing.
+const TASK_COMPLETED_MARKER: &str = "Task completed > TaskHandler[";
+/// Prefix of the trace-level line recording a published output file.
+const PUBLISH_PREFIX: &str = "publishing file: ";
+/// Marker of the last line Nextflow writes on a normal shutdown.
+const GOODBYE_MARKER: &str = "Execution complete -- Goodbye";
+/// Timestamp layout used at the start of every log line, e.g. `Oct-18 10:00:00.000`.
+const TIMESTAMP_FORMAT: &str = "%Y-%b-%d %H:%M:%S%.3f";
+/// Length of the timestamp at the start of every log line.
+const TIMESTAMP_LEN: usize = 19;
+/// Year assumed when the run's is unknown; a leap year, so `Feb-29` parses.
+const DEFAULT_YEAR: i32 = 2000;
+
+/// One thing that happened during a run.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum LogEvent {
+    /// The `nextflow` command line that started the run.
+    Command { command: String },
+    /// Version of Nextflow itself.
+    NextflowVersion { version: String },
+    /// The `Launching ...` line naming the workflow and run.
+    Launch {
+        run_name: String,
+        project: String,
+        workflow: String,
+        revision: String,
+    },
+    /// Session UUID shared by a run and any `-resume` of it.
+    Session { session_id: String },
+    /// A configuration profile applied with `-profile`.
+    Profile { profile: String },
+    /// A configuration file read at launch.
+    ConfigFile { path: String },
+    /// An executor tasks were dispatched to.
+    Executor { executor: String },
+    /// The workflow's own version, as printed in its banner.
+    WorkflowVersion { version: String },
+    /// A task was handed to the executor.
+    TaskSubmitted {
+        hash: String,
+        name: String,
+        process: String,
+    },
+    /// A task's result was reused from an earlier run.
+    TaskCached {
+        hash: String,
+        name: String,
+        process: String,
+    },
+    /// A task finished successfully.
+    TaskCompleted {
+        hash: String,
+        name: String,
+        process: String,
+        exit: String,
+    },
+    /// A task finished with a non-zero exit status or an error.
+    TaskFailed {
+        hash: String,
+        name: String,
+        process: String,
+        exit: String,
+    },
+    /// An output file was published from a task's work directory.
+    FilePublished { source: String, target: String },
+    /// A `WARN` entry.
+    Warning { message: String },
+    /// An `ERROR` entry.
+    Error { message: String },
+    /// The run shut down.
+    Completed {
+        /// Whether any `ERROR` entry was logged before shutdown. The log does
+        /// not record an exit status; the run history does.
+        errors_logged: bool,
+        duration: Option<String>,
+    },
+}
+
+impl LogEvent {
+    /// Short name of the event kind, as used in the serialized `event` tag.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LogEvent::Command { .. } => "command",
+            LogEvent::NextflowVersion { .. } => "nextflow_version",
+            LogEvent::Launch { .. } => "launch",
+            LogEvent::Session { .. } => "session",
+            LogEvent::Profile { .. } => "profile",
+            LogEvent::ConfigFile { .. } => "config_file",
+            LogEvent::Executor { .. } => "executor",
+            LogEvent::WorkflowVersion { .. } => "workflow_version",
+            LogEvent::TaskSubmitted { .. } => "task_submitted",
+            LogEvent::TaskCached { .. } => "task_cached",
+            LogEvent::TaskCompleted { .. } => "task_completed",
+            LogEvent::TaskFailed { .. } => "task_failed",
+            LogEvent::FilePublished { .. } => "file_published",
+            LogEvent::Warning { .. } => "warning",
+            LogEvent::Error { .. } => "error",
+            LogEvent::Completed { .. } => "completed",
+        }
+    }
+
+    /// One-line human description of the event's payload.
+    pub fn detail(&self) -> String {
+        match self {
+            LogEvent::Command { command } => command.clone(),
+            LogEvent::NextflowVersion { version } | LogEvent::WorkflowVersion { version } => {
+                version.clone()
+            }
+            LogEvent::Launch {
+                run_name,
+                project,
+                workflow,
+                revision,
+            } => format!("{project}/{workflow} [{run_name}] revision {revision}"),
+            LogEvent::Session { session_id } => session_id.clone(),
+            LogEvent::Profile { profile } => profile.clone(),
+            LogEvent::ConfigFile { path } => path.clone(),
+            LogEvent::Executor { executor } => executor.clone(),
+            LogEvent::TaskSubmitted { hash, name, .. }
+            | LogEvent::TaskCached { hash, name, .. } => {
+                format!("[{hash}] {name}")
+            }
+            LogEvent::TaskCompleted {
+                hash, name, exit, ..
+            }
+            | LogEvent::TaskFailed {
+                hash, name, exit, ..
+            } => format!("[{hash}] {name} exit {exit}"),
+            LogEvent::FilePublished { source, target } => format!("{source} -> {target}"),
+            LogEvent::Warning { message } | LogEvent::Error { message } => message.clone(),
+            LogEvent::Completed {
+                errors_logged,
+                duration,
+            } => {
+                let outcome = if *errors_logged {
+                    "errors logged"
+                } else {
+                    "no errors logged"
+                };
+                match duration {
+                    Some(duration) => format!("{outcome} after {duration}"),
+                    None => outcome.to_owned(),
+                }
+            }
+        }
+    }
+}
+
+/// An event with the log timestamp it was recorded at.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TimelineEvent {
+    /// Timestamp as written in the log, without a year.
+    pub timestamp: Option<String>,
+    #[serde(flatten)]
+    pub event: LogEvent,
+}
+
+/// All events recovered from one log, in the order they were written.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NextflowTimeline {
+    pub events: Vec<TimelineEvent>,
+}
+
+impl NextflowTimeline {
+    /// Parses the text of a `.nextflow.log` or a reduced `nextflow.stdout`.
+    ///
+    /// Lines without the usual `timestamp [thread] LEVEL logger - ` prefix,
+    /// such as continuation lines or the reduced transcript, are matched on
+    /// their text alone and carry no timestamp.
+    pub fn parse(log: &str) -> Self {
+        Self::parse_in_year(log, None)
+    }
+
+    /// Parses a log written by a run that started in `year`.
+    ///
+    /// Log timestamps have no year, so it is taken from the run history and
+    /// advanced when the timestamps jump back, as they do at New Year. The
+    /// run duration is only right across New Year when this is known; `None`
+    /// assumes a leap year.
+    pub fn parse_in_year(log: &str, year: Option<i32>) -> Self {
+        let mut year = year.unwrap_or(DEFAULT_YEAR);
+        let mut events: Vec<TimelineEvent> = Vec::new();
+        let mut workflow = String::new();
+        let mut first_time: Option<NaiveDateTime> = None;
+        let mut last_time: Option<NaiveDateTime> = None;
+        let mut errors = false;
+
+        for line in log.lines() {
+            let entry = LogLine::parse(line);
+            if let Some(mut time) = entry
+                .timestamp
+                .and_then(|timestamp| parse_timestamp(timestamp, year))
+            {
+                // threads log slightly out of order; only a large jump back is a new year
+                if last_time.is_some_and(|last| time < last - Duration::days(1)) {
+                    year += 1;
+                    time = time.with_year(year).unwrap_or(time);
+                }
+                first_time.get_or_insert(time);
+                last_time = Some(time);
+            }
+
+            let mut push = |event: LogEvent| {
+                events.push(TimelineEvent {
+                    timestamp: entry.timestamp.map(str::to_owned),
+                    event,
+                })
+            };
+
+            if let Some(launch) = parse_launch_line(entry.message) {
+                workflow = launch.pname.clone();
+                push(LogEvent::Launch {
+                    run_name: launch.name,
+                    project: launch.project,
+                    workflow: launch.pname,
+                    revision: launch.revision,
+                });
+            } else if let Some(event) = classify(entry.message, &workflow) {
+                push(event);
+            } else if entry.message.contains(GOODBYE_MARKER) {
+                let duration = first_time
+                    .zip(last_time)
+                    .map(|(first, last)| format_duration((last - first).num_seconds()));
+                push(LogEvent::Completed {
+                    errors_logged: errors,
+                    duration,
+                });
+            } else if entry.level == Some("WARN") {
+                push(LogEvent::Warning {
+                    message: entry.message.to_owned(),
+                });
+            } else if entry.level == Some("ERROR") || entry.message.starts_with("ERROR ~ ") {
+                errors = true;
+                push(LogEvent::Error {
+                    message: entry.message.trim_start_matches("ERROR ~ ").to_owned(),
+                });
+            }
+        }
+
+        NextflowTimeline { events }
+    }
+
+    /// Returns the first launch event, if the log has one.
+    pub fn launch(&self) -> Option<&LogEvent> {
+        self.events
+            .iter()
+            .map(|entry| &entry.event)
+            .find(|event| matches!(event, LogEvent::Launch { .. }))
+    }
+
+    /// Returns the session UUID, if the log has one.
+    pub fn session_id(&self) -> Option<&str> {
+        self.events.iter().find_map(|entry| match &entry.event {
+            LogEvent::Session { session_id } => Some(session_id.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns the workflow version printed in its banner, if any.
+    pub fn workflow_version(&self) -> Option<&str> {
+        self.events.iter().find_map(|entry| match &entry.event {
+            LogEvent::WorkflowVersion { version } => Some(version.as_str()),
+            _ => None,
+        })
+    }
+}
+
+/// A log line split into the parts Nextflow's logger writes.
+struct LogLine<'a> {
+    timestamp: Option<&'a str>,
+    level: Option<&'a str>,
+    message: &'a str,
+}
+
+impl<'a> LogLine<'a> {
+    fn parse(line: &'a str) -> Self {
+        let bare = LogLine {
+            timestamp: None,
+            level: None,
+            message: line.trim(),
+        };
+        let Some(timestamp) = line.get(..TIMESTAMP_LEN).filter(|ts| is_timestamp(ts)) else {
+            return bare;
+        };
+        // thread names such as `Task submitter` contain spaces, so split on `] `
+        let Some((_, rest)) = line[TIMESTAMP_LEN..].split_once("] ") else {
+            return bare;
+        };
+        let rest = rest.trim_start();
+        let (level, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+        let message = rest.split_once(" - ").map_or(rest, |(_, message)| message);
+        LogLine {
+            timestamp: Some(timestamp),
+            level: Some(level),
+            message: message.trim(),
+        }
+    }
+}
+
+/// Recognises the message of every event other than launch, errors, warnings
+/// and completion.
+fn classify(message: &str, workflow: &str) -> Option<LogEvent> {
+    if let Some(command) = message.strip_prefix(COMMAND_PREFIX) {
+        return Some(LogEvent::Command {
+            command: command.trim().to_owned(),
+        });
+    }
+    if let Some(version) = message.strip_prefix(NEXTFLOW_VERSION_PREFIX) {
+        return Some(LogEvent::NextflowVersion {
+            version: version.trim().to_owned(),
+        });
+    }
+    if let Some(session_id) = message.strip_prefix(SESSION_PREFIX) {
+        return Some(LogEvent::Session {
+            session_id: session_id.trim().to_owned(),
+        });
+    }
+    if let Some(path) = message.strip_prefix(CONFIG_FILE_PREFIX) {
+        return Some(LogEvent::ConfigFile {
+            path: path.trim().to_owned(),
+        });
+    }
+    if let Some(profile) = message.strip_prefix(PROFILE_PREFIX) {
+        return Some(LogEvent::Profile {
+            profile: profile.trim().trim_matches('`').to_owned(),
+        });
+    }
+    if let Some((_, executor)) = message.split_once(EXECUTOR_MARKER) {
+        return Some(LogEvent::Executor {
+            executor: executor.trim().to_owned(),
+        });
+    }
+    if let Some((hash, name)) = task_line(message, SUBMITTED_MARKER) {
+        return Some(LogEvent::TaskSubmitted {
+            process: process_of(&name),
+            hash,
+            name,
+        });
+    }
+    if let Some((hash, name)) = task_line(message, CACHED_MARKER) {
+        return Some(LogEvent::TaskCached {
+            process: process_of(&name),
+            hash,
+            name,
+        });
+    }
+    if let Some(published) = strip_prefix_ignore_case(message, PUBLISH_PREFIX) {
+        return published_file(published);
+    }
+    if let Some((_, handler)) = message.split_once(TASK_COMPLETED_MARKER) {
+        return task_completed(handler);
+    }
+    if !workflow.is_empty() && message.contains(VERSION_MARKER) && message.contains(workflow) {
+        return Some(LogEvent::WorkflowVersion {
+            version: parse_version(message, workflow),
+        });
+    }
+    None
+}
+
+/// Splits `[ab/cdef12] Submitted process > name (1)` into hash and task name.
+fn task_line(message: &str, marker: &str) -> Option<(String, String)> {
+    let (prefix, name) = message.split_once(marker)?;
+    let hash = prefix.trim().trim_start_matches('[').trim_end_matches(']');
+    Some((hash.to_owned(), name.trim().to_owned()))
+}
+
+/// Parses the `TaskHandler[id: 1; name: foo (1); status: COMPLETED; exit: 0; ...]` payload.
+fn task_completed(handler: &str) -> Option<LogEvent> {
+    let handler = handler.trim_end().trim_end_matches(']');
+    let field = |key: &str| {
+        handler
+            .split("; ")
+            .find_map(|part| part.strip_prefix(key))
+            .map(|value| value.trim().to_owned())
+    };
+    let name = field("name: ")?;
+    let exit = field("exit: ").unwrap_or_else(|| String::from("-"));
+    let error = field("error: ").unwrap_or_else(|| String::from("-"));
+    let hash = field("workDir: ")
+        .map(|work_dir| short_hash(&work_dir))
+        .unwrap_or_default();
+    let process = process_of(&name);
+
+    if exit == "0" && error == "-" {
+        Some(LogEvent::TaskCompleted {
+            hash,
+            name,
+            process,
+            exit,
+        })
+    } else {
+        Some(LogEvent::TaskFailed {
+            hash,
+            name,
+            process,
+            exit,
+        })
+    }
+}
+
+/// Parses `source -[copy]-> target` or `source -> target`.
+fn published_file(published: &str) -> Option<LogEvent> {
+    let (source, target) = published.split_once(" -")?;
+    let target = target.split_once("> ")?.1;
+    Some(LogEvent::FilePublished {
+        source: source.trim().to_owned(),
+        target: target.trim().to_owned(),
+    })
+}
+
+fn strip_prefix_ignore_case<'a>(message: &'a str, prefix: &str) -> Option<&'a str> {
+    let head = message.get(..prefix.len())?;
+    head.eq_ignore_ascii_case(prefix)
+        .then(|| &message[prefix.len()..])
+}
+
+/// Abbreviates a task work directory to the `ab/cdef12` form used in the log.
+fn short_hash(work_dir: &str) -> String {
+    let mut parts = work_dir.trim_end_matches('/').rsplit('/');
+    match (parts.next(), parts.next()) {
+        (Some(hash), Some(prefix)) => {
+            format!("{prefix}/{}", hash.get(..6).unwrap_or(hash))
+        }
+        _ => String::new(),
+    }
+}
+
+/// Strips the ` (tag)` suffix Nextflow appends to task names.
+fn process_of(name: &str) -> String {
+    name.split_once(" (")
+        .map_or(name, |(process, _)| process)
+        .trim()
+        .to_owned()
+}
+
+fn is_timestamp(candidate: &str) -> bool {
+    let bytes = candidate.as_bytes();
+    bytes.len() == TIMESTAMP_LEN
+        && bytes[3] == b'-'
+        && bytes[6] == b' '
+        && bytes[9] == b':'
+        && bytes[15] == b'.'
+}
+
+/// Parses a log timestamp, which has no year of its own, as one in `year`.
+fn parse_timestamp(timestamp: &str, year: i32) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(&format!("{year}-{timestamp}"), TIMESTAMP_FORMAT).ok()
+}
+
+/// Formats seconds the way Nextflow reports durations, e.g. `1h 2m 3s`.
+fn format_duration(seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match (hours, minutes) {
+        (0, 0) => format!("{seconds}s"),
+        (0, _) => format!("{minutes}m {seconds}s"),
+        _ => format!("{hours}h {minutes}m {seconds}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogEvent, NextflowTimeline};
+
+    const LOG: &str = "\
+Oct-18 10:00:00.000 [main] DEBUG nextflow.cli.Launcher - $> nextflow run epi2me-labs/wf-alignment -profile standard --out_dir output
+Oct-18 10:00:01.000 [main] INFO  nextflow.cli.CmdRun - N E X T F L O W  ~  version 23.04.2
+Oct-18 10:00:01.500 [main] DEBUG nextflow.config.ConfigBuilder - Parsing config file: /runs/nextflow.config
+Oct-18 10:00:01.600 [main] INFO  nextflow.config.ConfigBuilder - Applying config profile: `standard`
+Oct-18 10:00:02.000 [main] INFO  nextflow.cli.CmdRun - Launching `https://github.com/epi2me-labs/wf-alignment` [clever_ampere] DSL2 - revision: abc1234567 [master]
+Oct-18 10:00:03.000 [main] DEBUG nextflow.Session - Session UUID: 11111111-2222-3333-4444-555555555555
+Oct-18 10:00:03.500 [main] INFO  nextflow.Nextflow - |||||||||| wf-alignment 1.1.0-gabc
+Oct-18 10:00:03.800 [main] INFO  nextflow.executor.Executor - [warm up] executor > local
+Oct-18 10:00:04.000 [Task submitter] INFO  nextflow.Session - [ab/cdef12] Submitted process > alignment:minimap2 (1)
+Oct-18 10:00:05.000 [main] INFO  nextflow.processor.TaskProcessor - [cd/ef0123] Cached process > makeReport
+Oct-18 10:00:30.000 [Task monitor] DEBUG n.processor.TaskPollingMonitor - Task completed > TaskHandler[id: 1; name: alignment:minimap2 (1); status: COMPLETED; exit: 0; error: -; workDir: /runs/work/ab/cdef12345678]
+Oct-18 10:00:40.000 [Task monitor] WARN  nextflow.processor.TaskProcessor - Process `makeReport` ignored an error
+Oct-18 10:01:02.000 [main] DEBUG nextflow.script.ScriptRunner - > Execution complete -- Goodbye
+";
+
+    #[test]
+    fn recovers_typed_events_in_order() {
+        let timeline = NextflowTimeline::parse(LOG);
+        let kinds: Vec<&str> = timeline
+            .events
+            .iter()
+            .map(|entry| entry.event.kind())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "command",
+                "nextflow_version",
+                "config_file",
+                "profile",
+                "launch",
+                "session",
+                "workflow_version",
+                "executor",
+                "task_submitted",
+                "task_cached",
+                "task_completed",
+                "warning",
+                "completed"
+            ]
+        );
+        assert_eq!(
+            timeline.session_id(),
+            Some("11111111-2222-3333-4444-555555555555")
+        );
+        assert_eq!(timeline.workflow_version(), Some("1.1.0"));
+        assert_eq!(
+            timeline.events[10].event,
+            LogEvent::TaskCompleted {
+                hash: String::from("ab/cdef12"),
+                name: String::from("alignment:minimap2 (1)"),
+                process: String::from("alignment:minimap2"),
+                exit: String::from("0"),
+            }
+        );
+        assert_eq!(
+            timeline.events[12].event,
+            LogEvent::Completed {
+                errors_logged: false,
+                duration: Some(String::from("1m 2s")),
+            }
+        );
+    }
+
+    #[test]
+    fn reports_failed_tasks_and_errors() {
+        let log = "\
+Oct-18 11:00:10.000 [Task monitor] DEBUG n.processor.TaskPollingMonitor - Task completed > TaskHandler[id: 2; name: align (2); status: COMPLETED; exit: 137; error: -; workDir: /runs/work/ef/0123456789]
+Oct-18 11:00:10.100 [Task monitor] ERROR nextflow.processor.TaskProcessor - Error executing process > 'align (2)'
+Oct-18 11:00:12.000 [main] DEBUG nextflow.script.ScriptRunner - > Execution complete -- Goodbye
+";
+        let timeline = NextflowTimeline::parse(log);
+        assert!(matches!(
+            &timeline.events[0].event,
+            LogEvent::TaskFailed { exit, hash, .. } if exit == "137" && hash == "ef/012345"
+        ));
+        assert_eq!(timeline.events[1].event.kind(), "error");
+        assert_eq!(
+            timeline.events[2].event,
+            LogEvent::Completed {
+                errors_logged: true,
+                duration: Some(String::from("2s")),
+            }
+        );
+    }
+
+    #[test]
+    fn carries_the_year_across_new_year() {
+        let log = "Dec-31 23:59:00.000 [main] DEBUG nextflow.cli.Launcher - $> nextflow run wf
+Dec-31 23:58:59.900 [Task monitor] DEBUG nextflow.Session - late line from another thread
+Jan-01 00:01:30.000 [main] DEBUG nextflow.script.ScriptRunner - > Execution complete -- Goodbye
+";
+        let timeline = NextflowTimeline::parse_in_year(log, Some(2025));
+        assert_eq!(
+            timeline.events.last().map(|entry| &entry.event),
+            Some(&LogEvent::Completed {
+                errors_logged: false,
+                duration: Some(String::from("2m 30s")),
+            })
+        );
+    }
+}
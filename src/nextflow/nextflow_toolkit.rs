@@ -6,32 +6,65 @@
 //! This module is the adapter that starts from a plain `nextflow log` view and
 //! progressively reconstructs enough EPI2ME-shaped state for import.
 
-use std::{fs, io::Cursor, path::PathBuf, process::Command};
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 use ulid::Ulid;
-use walkdir::WalkDir;
 
 use crate::{
+    app_db::Epi2MeAnalysis,
     bundle,
-    dataframe::{self, nextflow_vec_to_df},
-    epi4you_errors::Epi4youError,
+    epi2me_db::Epi2meSetup,
+    epi2me_desktop_analysis::Epi2meDesktopAnalysis,
+    epi4you_errors::{Epi4youError, ErrorSource},
     nextflow::{
-        nextflow_analysis::NextflowAnalysis,
+        nextflow_analysis::{desktop_status, find_run_log, NextflowAnalysis},
+        nextflow_command::NextflowCommand,
+        nextflow_execution::ExecutionDetails,
+        nextflow_history,
         nextflow_log_item::{NxfLogItem, Row},
+        nextflow_reports,
+        nextflow_resources::{self, ResourceSummary},
+        nextflow_timeline::NextflowTimeline,
+        nextflow_work::{self, WorkMode},
     },
+    nextflow_log_parser::WorkflowSource,
     tempdir::TempDir,
+    xmanifest::Epi2MeManifest,
 };
 
+/// Choices that shape how a CLI run is bundled.
+#[derive(Clone, Debug, Default)]
+pub struct BundleOptions {
+    /// Overwrite an existing archive.
+    pub force: bool,
+    /// Bundle runs that failed or were cancelled instead of refusing them.
+    pub include_failed: bool,
+    /// Output folder of the run, overriding detection; relative to the current
+    /// directory.
+    pub out_dir: Option<PathBuf>,
+    /// `.nextflow.log*` file of the run, overriding log selection; relative to
+    /// the current directory.
+    pub nxf_log: Option<PathBuf>,
+    /// Task work directories to bundle for troubleshooting.
+    pub with_work: WorkMode,
+    /// Also bundle the files tasks produced, not only their scripts and logs.
+    pub work_outputs: bool,
+}
+
 /// Snapshot of a directory that contains one or more local Nextflow runs.
 ///
 /// The struct owns:
 ///
 /// - the directory we will inspect,
-/// - the resolved `nextflow` binary used to inspect it, and
-/// - parsed `nextflow log` rows that represent successful candidate runs.
+/// - an explicit `nextflow` binary for the `nextflow log` fallback, and
+/// - parsed history rows for every run, whatever its status.
 pub struct NextFlowResultFolder {
     folder: PathBuf,
-    nxf_bin: PathBuf,
+    nxf_bin: Option<String>,
     vec: Vec<NxfLogItem>,
 }
 
@@ -49,7 +82,7 @@ impl NextFlowResultFolder {
 
         let mut folder = NextFlowResultFolder {
             folder,
-            nxf_bin: NextFlowResultFolder::get_nextflow_path(nxf_bin)?,
+            nxf_bin,
             vec: Vec::<NxfLogItem>::new(),
         };
         folder.parse_nextflow_folder()?;
@@ -77,7 +110,9 @@ impl NextFlowResultFolder {
             let output = Command::new("which")
                 .arg("nextflow")
                 .output()
-                .map_err(|_| Epi4youError::FailedToRunCommand(String::from("which nextflow")))?;
+                .map_err(|err| {
+                    Epi4youError::FailedToRunCommand(String::from("which nextflow"), err.into())
+                })?;
 
             let mut s = String::from_utf8_lossy(&output.stdout).into_owned();
             if s.ends_with('\n') {
@@ -106,47 +141,61 @@ impl NextFlowResultFolder {
         }
     }
 
-    /// Executes `nextflow log` and retains only successful run records.
+    /// Reads the run history, keeping failed and cancelled runs alongside
+    /// successful ones.
     ///
-    /// The resulting in-memory index is what powers `--list` and `--runid`
-    /// selection in the CLI capture flow.
+    /// `.nextflow/history` is read directly where it exists; otherwise we fall
+    /// back to asking `nextflow log`. The resulting in-memory index is what
+    /// powers `--list` and `--runid` selection in the CLI capture flow.
     fn parse_nextflow_folder(&mut self) -> Result<(), Epi4youError> {
         log::info!(
             "Looking for nxf artifacts at [{}]",
             &self.folder.to_string_lossy()
         );
 
-        let output = Command::new(&self.nxf_bin)
+        let items = match nextflow_history::read_history(&self.folder) {
+            Some(items) => items,
+            None => self.run_nextflow_log()?,
+        };
+        self.vec.extend(items);
+
+        Ok(())
+    }
+
+    /// Lists runs through `nextflow log` when no history file can be read.
+    fn run_nextflow_log(&self) -> Result<Vec<NxfLogItem>, Epi4youError> {
+        let nxf_bin = NextFlowResultFolder::get_nextflow_path(self.nxf_bin.clone())?;
+        let output = Command::new(&nxf_bin)
             .current_dir(&self.folder)
             .arg("log")
             .output()
-            .map_err(|_| Epi4youError::FailedToRunCommand(String::from("nextflow log")))?;
+            .map_err(|err| {
+                Epi4youError::FailedToRunCommand(String::from("nextflow log"), err.into())
+            })?;
 
         if !output.status.success() {
-            return Err(Epi4youError::FailedToRunCommand(String::from(
-                "nextflow log",
-            )));
+            return Err(Epi4youError::FailedToRunCommand(
+                String::from("nextflow log"),
+                ErrorSource::msg(String::from_utf8_lossy(&output.stderr).trim()),
+            ));
         }
 
         let file = Cursor::new(output.stdout);
         let mut rdr = csv::ReaderBuilder::new().delimiter(b'\t').from_reader(file);
 
+        let mut items = Vec::new();
         for record in rdr.records().flatten() {
             if let Ok(row) = record.deserialize::<Row>(None) {
-                let item = NxfLogItem::init(row.clone())?;
-                if row.get_status().trim() == "OK" {
-                    self.vec.push(item);
-                }
+                items.push(NxfLogItem::init(row)?);
             }
         }
 
-        Ok(())
+        Ok(items)
     }
 
-    /// Prints the discovered successful runs in tabular form.
-    pub fn list_runs(&self) {
-        let df = nextflow_vec_to_df(self.vec.clone());
-        dataframe::print_polars_df(&df);
+    /// Returns the discovered runs in launch order.
+    pub fn get_runs(&self) -> &[NxfLogItem] {
+        &self.vec
     }
 
     /// Finds one parsed run by its Nextflow run name.
@@ -161,6 +210,42 @@ impl NextFlowResultFolder {
             .ok_or(Epi4youError::SpecifiedNextflowRunNotFound(runid))
     }
 
+    /// Parses the `.nextflow.log` of one run into its event timeline.
+    ///
+    /// `nxf_log` names the log to read instead of searching for it.
+    pub fn timeline(
+        &self,
+        wf_analysis: &NxfLogItem,
+        nxf_log: Option<&Path>,
+    ) -> Result<NextflowTimeline, Epi4youError> {
+        let (log_path, content) = find_run_log(&self.folder, wf_analysis, nxf_log)?;
+        log::info!("reading timeline from [{}]", log_path.display());
+        Ok(NextflowTimeline::parse_in_year(
+            &content,
+            wf_analysis.start_year(),
+        ))
+    }
+
+    /// Summarises the resources one run used from its execution trace.
+    ///
+    /// Returns `None` when no trace of this run is found below its output
+    /// folder.
+    pub fn resources(
+        &self,
+        wf_analysis: &NxfLogItem,
+        options: &BundleOptions,
+    ) -> Result<Option<ResourceSummary>, Epi4youError> {
+        let analysis = NextflowAnalysis::init(
+            wf_analysis.clone(),
+            self.folder.clone(),
+            options.out_dir.as_deref(),
+            options.nxf_log.as_deref(),
+        )?;
+        Ok(analysis
+            .find_trace()
+            .and_then(|records| nextflow_resources::summarise(&records)))
+    }
+
     /// Bundles one selected CLI run as a `.2me` archive.
     ///
     /// Conceptually this method performs four translations:
@@ -169,61 +254,163 @@ impl NextFlowResultFolder {
     /// 2. distill the Nextflow log into EPI2ME-like helper files,
     /// 3. stage output files into a temporary EPI2ME-style layout, and
     /// 4. delegate final manifest/tar creation to the bundle layer.
+    ///
+    /// Runs that did not finish `OK` are refused unless
+    /// [`BundleOptions::include_failed`] is set. The manifest written into
+    /// the archive is returned for reporting.
     pub fn bundle_cli_run(
         &self,
+        setup: Option<&Epi2meSetup>,
         temp_dir: &TempDir,
         wf_analysis: NxfLogItem,
         twome: &str,
-        force: &bool,
-    ) -> Result<(), Epi4youError> {
+        options: &BundleOptions,
+    ) -> Result<Epi2MeManifest, Epi4youError> {
+        // resolved now so the checks below and the archive agree on one file
+        let dest = std::path::absolute(twome)
+            .map_err(|err| Epi4youError::FailedToWritePath(PathBuf::from(twome), err.into()))?;
+        if dest.exists() && !options.force {
+            log::error!(
+                "twome destination [{:?}] already exists - use `--force`?",
+                dest
+            );
+            return Err(Epi4youError::FileAlreadyExistsUnforcedExecution(dest));
+        }
+
+        let (vehicle, outputs) = self.stage_cli_run(temp_dir, wf_analysis, options)?;
+        bundle::export_cli_run(
+            setup,
+            vehicle,
+            temp_dir.path.clone(),
+            outputs,
+            temp_dir.clone(),
+            dest,
+            &options.force,
+        )
+    }
+
+    /// Imports one selected CLI run straight into the local Desktop.
+    ///
+    /// The run is staged as for [`NextFlowResultFolder::bundle_cli_run`], then
+    /// registered in `app.db` and copied into a new instance folder without
+    /// writing an archive. With `move_outputs` the files of the output folder
+    /// are moved instead, leaving the original run without them.
+    pub fn import_cli_run(
+        &self,
+        setup: &Epi2meSetup,
+        temp_dir: &TempDir,
+        wf_analysis: NxfLogItem,
+        options: &BundleOptions,
+        move_outputs: bool,
+    ) -> Result<Epi2MeAnalysis, Epi4youError> {
+        let (vehicle, outputs) = self.stage_cli_run(temp_dir, wf_analysis, options)?;
+        bundle::import_cli_run(setup, vehicle, temp_dir.path.clone(), outputs, move_outputs)
+    }
+
+    /// Writes the generated files of a run into `temp_dir` and describes it.
+    ///
+    /// Returns the analysis record and the run's output folder, which is left
+    /// where it is.
+    fn stage_cli_run(
+        &self,
+        temp_dir: &TempDir,
+        wf_analysis: NxfLogItem,
+        options: &BundleOptions,
+    ) -> Result<(Epi2meDesktopAnalysis, PathBuf), Epi4youError> {
+        if !wf_analysis.is_ok() && !options.include_failed {
+            log::error!(
+                "run [{}] has status [{}] - use `--include-failed` to bundle it",
+                wf_analysis.run_name,
+                wf_analysis.status
+            );
+            return Err(Epi4youError::NextflowRunNotSuccessful(
+                wf_analysis.run_name,
+                wf_analysis.status,
+            ));
+        }
+
         let ulid_str = Ulid::new().to_string();
-        let analysis = NextflowAnalysis::init(wf_analysis.clone(), self.folder.clone())?;
+        let analysis = NextflowAnalysis::init(
+            wf_analysis.clone(),
+            self.folder.clone(),
+            options.out_dir.as_deref(),
+            options.nxf_log.as_deref(),
+        )?;
 
         let nextflow_log_str = analysis.locate_nextflow_log(&temp_dir.path)?;
         let nextflow_stdout = analysis.extract_log_stdout(&nextflow_log_str, &temp_dir.path)?;
+        let status = desktop_status(&wf_analysis.status, &nextflow_log_str);
+        log::info!("run status [{}] maps to [{status}]", wf_analysis.status);
         let _progress_json =
             analysis.prepare_progress_json(&nextflow_stdout, &temp_dir.path, &ulid_str)?;
+        analysis.prepare_params_json(&nextflow_log_str, &temp_dir.path, &ulid_str)?;
 
-        let local_output = temp_dir.path.join("output");
-        fs::create_dir_all(&local_output)
-            .map_err(|_| Epi4youError::FailedToCreateFolder(local_output.clone()))?;
+        let command = NextflowCommand::parse(&wf_analysis.command);
+        let timeline = NextflowTimeline::parse_in_year(&nextflow_log_str, wf_analysis.start_year());
+        let mut execution =
+            ExecutionDetails::from_run(&wf_analysis, &command, &timeline, &self.vec);
+        execution.bundle_config_files(&self.folder, &temp_dir.path)?;
+        execution.output_dir = Some(analysis.get_analysis_dir().to_string_lossy().into_owned());
+        execution.output_dir_strategy = Some(analysis.get_output_dir_strategy());
 
-        log::info!("TempDir == {}", temp_dir);
-        log::info!("AnalysisPath == {:?}", &analysis.get_analysis_dir());
-        let analysis_dir = analysis.get_analysis_dir();
-        for entry in WalkDir::new(&analysis_dir).into_iter().flatten() {
-            if let Ok(relative_path) = entry.path().strip_prefix(&analysis_dir) {
-                let destination = local_output.join(relative_path);
-
-                if entry.path().is_dir() {
-                    fs::create_dir_all(&destination)
-                        .map_err(|_| Epi4youError::FailedToCreateFolder(destination.clone()))?;
-                } else if entry.path().is_file() {
-                    fs::copy(entry.path(), &destination)
-                        .map_err(|_| Epi4youError::FailedToWritePath(destination.clone()))?;
-                }
-            }
+        let trace = analysis.find_trace();
+        execution.resources = trace.as_deref().and_then(nextflow_resources::summarise);
+        if options.with_work != WorkMode::None {
+            let mut tasks: Vec<_> = nextflow_work::run_tasks(trace.as_deref(), &timeline)
+                .into_iter()
+                .filter(|task| options.with_work.selects(task))
+                .collect();
+            log::info!("bundling work directories of {} tasks", tasks.len());
+            nextflow_work::bundle_work_dirs(
+                &mut tasks,
+                &nextflow_work::work_dir(&command, &self.folder),
+                &temp_dir.path,
+                options.work_outputs,
+            )?;
+            execution.work_tasks = tasks;
         }
 
-        let dest = PathBuf::from(twome);
-        if dest.exists() && !*force {
-            log::error!(
-                "twome destination [{:?}] already exists - use `--force`?",
-                dest
-            );
-            return Err(Epi4youError::FileAlreadyExistsUnforcedExecution(dest));
-        }
+        let reports = nextflow_reports::find_reports(
+            &command,
+            &nextflow_log_str,
+            &self.folder,
+            &wf_analysis.timestamp,
+        );
+        execution.reports = nextflow_reports::bundle_reports(
+            &reports,
+            &analysis.get_analysis_dir(),
+            &temp_dir.path,
+        )?;
 
-        bundle::export_cli_run(
+        log::info!("TempDir == {}", temp_dir);
+        log::info!("AnalysisPath == {:?}", &analysis.get_analysis_dir());
+
+        let mut vehicle = Epi2meDesktopAnalysis::init(
             &ulid_str,
-            temp_dir.path.clone(),
-            temp_dir.clone(),
-            dest,
+            &temp_dir.path,
             &nextflow_stdout,
             &wf_analysis.timestamp,
-            force,
+            status,
         );
-
-        Ok(())
+        // the launch line names hosted workflows well, but not local scripts
+        let workflow = command.pipeline.as_deref().map(|pipeline| {
+            WorkflowSource::parse(pipeline, command.option("-hub")).with_launch_dir(&self.folder)
+        });
+        if let Some(workflow) = &workflow {
+            if workflow.script.is_some() || vehicle.workflowRepo.is_empty() {
+                vehicle.workflowUser = workflow.project.clone();
+                vehicle.workflowRepo = workflow.name.clone();
+            }
+            if !workflow.recognised_by_desktop {
+                log::warn!(
+                    "workflow [{}/{}] is not one Desktop installs - it can be browsed but not relaunched",
+                    vehicle.workflowUser,
+                    vehicle.workflowRepo
+                );
+            }
+        }
+        execution.workflow = workflow;
+        vehicle.execution = Some(Box::new(execution));
+        Ok((vehicle, analysis.get_analysis_dir()))
     }
 }
@@ -0,0 +1,458 @@
+//! Reader for Nextflow execution trace files.
+//!
+//! A run launched with `-with-trace` (which EPI2ME workflows enable by
+//! default) writes one tab-separated row per task attempt. Unlike the
+//! `.nextflow.log`, the trace records how every task ended, which is what
+//! Desktop's `progress.json` needs to show real per-process counts, and what
+//! each task cost in CPU time, memory and I/O.
+
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use walkdir::WalkDir;
+
+use super::{
+    nextflow_log_item::NxfLogItem,
+    nextflow_progress::ProgressItem,
+    nextflow_timeline::{LogEvent, NextflowTimeline},
+};
+
+/// How deep below the analysis folder trace files are searched for.
+const TRACE_SEARCH_DEPTH: usize = 3;
+/// Slack allowed after a run ends for its trace to be flushed.
+const TRACE_FLUSH_GRACE: Duration = Duration::from_secs(60);
+
+/// One task attempt recorded in a trace file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TraceRecord {
+    /// Short work-directory hash such as `ab/cdef12`.
+    pub hash: String,
+    /// Task name, the process name followed by its tag or index.
+    pub name: String,
+    /// Process name, derived from `name` when the trace has no `process` column.
+    pub process: String,
+    /// `COMPLETED`, `FAILED`, `ABORTED`, `CACHED`, `SUBMITTED` or `RUNNING`.
+    pub status: String,
+    /// Exit status of the task script, `-` if it never finished.
+    pub exit: String,
+    /// When the task was submitted, in milliseconds since the epoch.
+    pub submit: Option<i64>,
+    /// Milliseconds from submission to completion.
+    pub duration: Option<u64>,
+    /// Milliseconds the task script ran for.
+    pub realtime: Option<u64>,
+    /// CPU usage as a percentage of one core, so `350` is three and a half.
+    pub cpu_percent: Option<f64>,
+    /// Peak resident memory in bytes.
+    pub peak_rss: Option<u64>,
+    /// Peak virtual memory in bytes.
+    pub peak_vmem: Option<u64>,
+    /// Bytes read.
+    pub rchar: Option<u64>,
+    /// Bytes written.
+    pub wchar: Option<u64>,
+}
+
+impl TraceRecord {
+    /// Returns true if the task produced its outputs, in this run or an earlier one.
+    pub fn is_complete(&self) -> bool {
+        matches!(self.status.as_str(), "COMPLETED" | "CACHED")
+    }
+
+    /// Returns true if the task ended without producing its outputs.
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status.as_str(), "FAILED" | "ABORTED")
+    }
+}
+
+/// Finds the trace file written by a run below `folder`.
+///
+/// `trace.txt`, `execution_trace*.txt` and Nextflow's default `trace-*.txt`
+/// names are recognised. A resumed run leaves several behind, so the most
+/// recently modified one wins.
+pub fn find_trace(folder: &Path) -> Option<PathBuf> {
+    trace_files(folder).into_iter().next()
+}
+
+/// Finds and reads the trace written by `wf_analysis` below `folder`.
+///
+/// Every run publishing into a folder overwrites its `trace.txt`, and a
+/// resumed run leaves the trace of the run it resumed next to its own, so the
+/// newest trace need not be this run's. A trace is taken only if every task
+/// hash it records was submitted or cached according to the run's `nf_log`.
+/// A trace without a `hash` column must instead have been last written while
+/// the run was going, according to its history entry.
+pub fn find_run_trace(
+    folder: &Path,
+    wf_analysis: &NxfLogItem,
+    nf_log: &str,
+) -> Option<Vec<TraceRecord>> {
+    let timeline = NextflowTimeline::parse(nf_log);
+    let run_hashes: HashSet<&str> = timeline
+        .events
+        .iter()
+        .filter_map(|entry| match &entry.event {
+            LogEvent::TaskSubmitted { hash, .. } | LogEvent::TaskCached { hash, .. } => {
+                Some(hash.as_str())
+            }
+            _ => None,
+        })
+        .collect();
+
+    trace_files(folder).into_iter().find_map(|path| {
+        let records = read_trace(&path).filter(|records| !records.is_empty())?;
+        let belongs = if records.iter().any(|record| !record.hash.is_empty()) {
+            records
+                .iter()
+                .all(|record| run_hashes.contains(record.hash.as_str()))
+        } else {
+            written_during(&path, wf_analysis)
+        };
+        if !belongs {
+            log::info!(
+                "trace [{}] is not from run [{}] - skipping it",
+                path.display(),
+                wf_analysis.run_name
+            );
+        }
+        belongs.then_some(records)
+    })
+}
+
+/// Trace files below `folder`, most recently modified first.
+fn trace_files(folder: &Path) -> Vec<PathBuf> {
+    let mut traces: Vec<(Option<SystemTime>, PathBuf)> = WalkDir::new(folder)
+        .max_depth(TRACE_SEARCH_DEPTH)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file() && is_trace_name(entry.file_name()))
+        .map(|entry| {
+            let modified = entry.metadata().ok().and_then(|meta| meta.modified().ok());
+            (modified, entry.into_path())
+        })
+        .collect();
+    traces.sort_by_key(|(modified, _)| Reverse(*modified));
+    traces.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Returns true if `path` was last modified between the start and the end of
+/// the run, as recorded in the Nextflow history.
+fn written_during(path: &Path, wf_analysis: &NxfLogItem) -> bool {
+    let Some(started) =
+        NaiveDateTime::parse_from_str(wf_analysis.timestamp.trim(), "%Y-%m-%d %H:%M:%S")
+            .ok()
+            .and_then(|time| Local.from_local_datetime(&time).earliest())
+            .map(SystemTime::from)
+    else {
+        return false;
+    };
+    let Some(modified) = path.metadata().and_then(|meta| meta.modified()).ok() else {
+        return false;
+    };
+    let ended = parse_duration(&wf_analysis.duration)
+        .map(|millis| started + Duration::from_millis(millis) + TRACE_FLUSH_GRACE);
+    modified >= started && ended.is_none_or(|ended| modified <= ended)
+}
+
+/// Reads and parses the trace file at `path`.
+pub fn read_trace(path: &Path) -> Option<Vec<TraceRecord>> {
+    match fs::read_to_string(path) {
+        Ok(content) => {
+            log::info!("reading execution trace from [{}]", path.display());
+            Some(parse_trace(&content))
+        }
+        Err(err) => {
+            log::warn!("unable to read trace [{}] - {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Parses the content of a trace file.
+///
+/// Columns are located through the header line because the set of fields is
+/// configurable with `trace.fields`; only `name` and `status` are required.
+/// Resource columns are read both as Nextflow formats them (`1.5 GB`,
+/// `1m 3s`) and as written with `trace.raw` (bytes, milliseconds).
+pub fn parse_trace(content: &str) -> Vec<TraceRecord> {
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    let Some(header) = lines.next() else {
+        return Vec::new();
+    };
+    let columns: HashMap<&str, usize> = header
+        .split('\t')
+        .enumerate()
+        .map(|(idx, name)| (name.trim(), idx))
+        .collect();
+    let (Some(&name_col), Some(&status_col)) = (columns.get("name"), columns.get("status")) else {
+        log::warn!("trace header has no name or status column - ignoring trace");
+        return Vec::new();
+    };
+
+    lines
+        .map(|line| {
+            let cells: Vec<&str> = line.split('\t').collect();
+            let cell = |col: Option<&usize>| {
+                col.and_then(|idx| cells.get(*idx))
+                    .map(|value| value.trim().to_owned())
+                    .unwrap_or_default()
+            };
+            let name = cell(Some(&name_col));
+            let process = Some(cell(columns.get("process")))
+                .filter(|process| !process.is_empty())
+                .unwrap_or_else(|| process_of(&name).to_owned());
+            TraceRecord {
+                hash: cell(columns.get("hash")),
+                name,
+                process,
+                status: cell(Some(&status_col)),
+                exit: cell(columns.get("exit")),
+                submit: parse_timestamp(&cell(columns.get("submit"))),
+                duration: parse_duration(&cell(columns.get("duration"))),
+                realtime: parse_duration(&cell(columns.get("realtime"))),
+                cpu_percent: parse_percent(&cell(columns.get("%cpu"))),
+                peak_rss: parse_memory(&cell(columns.get("peak_rss"))),
+                peak_vmem: parse_memory(&cell(columns.get("peak_vmem"))),
+                rchar: parse_memory(&cell(columns.get("rchar"))),
+                wchar: parse_memory(&cell(columns.get("wchar"))),
+            }
+        })
+        .collect()
+}
+
+/// Builds per-process progress in the order processes first appear.
+///
+/// A retried task appears once per attempt under the same name; only its last
+/// attempt counts. A process is `COMPLETED` once all of its tasks are, and
+/// `FAILED` if any task ended without outputs.
+pub fn progress_from_trace(records: &[TraceRecord]) -> Vec<(String, ProgressItem)> {
+    let mut last_attempt: HashMap<&str, &TraceRecord> = HashMap::new();
+    let mut order: Vec<&str> = Vec::new();
+    for record in records {
+        if last_attempt.insert(&record.name, record).is_none() {
+            order.push(&record.name);
+        }
+    }
+
+    let mut processes: Vec<(String, ProgressItem)> = Vec::new();
+    for name in order {
+        let record = last_attempt[name];
+        let idx = match processes.iter().position(|(key, _)| *key == record.process) {
+            Some(idx) => idx,
+            None => {
+                processes.push((
+                    record.process.clone(),
+                    ProgressItem {
+                        status: String::new(),
+                        tag: String::from("null"),
+                        total: 0,
+                        complete: 0,
+                        failed: 0,
+                        cached: 0,
+                    },
+                ));
+                processes.len() - 1
+            }
+        };
+        let item = &mut processes[idx].1;
+        item.total = item.total.saturating_add(1);
+        if record.is_complete() {
+            item.complete = item.complete.saturating_add(1);
+        }
+        if record.is_failed() {
+            item.failed = item.failed.saturating_add(1);
+        }
+        if record.status == "CACHED" {
+            item.cached = item.cached.saturating_add(1);
+        }
+    }
+
+    for (_, item) in processes.iter_mut() {
+        item.status = String::from(if item.failed > 0 {
+            "FAILED"
+        } else if item.complete == item.total {
+            "COMPLETED"
+        } else {
+            "RUNNING"
+        });
+    }
+    processes
+}
+
+/// Strips the ` (tag)` suffix Nextflow appends to task names.
+fn process_of(name: &str) -> &str {
+    name.split_once(" (")
+        .map_or(name, |(process, _)| process)
+        .trim()
+}
+
+/// Reads a submission time, either epoch milliseconds or `2026-10-18 10:00:04.000`.
+fn parse_timestamp(value: &str) -> Option<i64> {
+    value.parse().ok().or_else(|| {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f")
+            .ok()
+            .map(|time| time.and_utc().timestamp_millis())
+    })
+}
+
+/// Reads a duration in milliseconds, either a number or `1d 2h 3m 4.5s`.
+fn parse_duration(value: &str) -> Option<u64> {
+    if let Ok(millis) = value.parse::<f64>() {
+        return Some(millis as u64);
+    }
+    let mut millis = 0.0;
+    for part in value.split_whitespace() {
+        let split = part.find(|c: char| c.is_ascii_alphabetic())?;
+        let (number, unit) = part.split_at(split);
+        let scale = match unit {
+            "ms" => 1.0,
+            "s" => 1_000.0,
+            "m" => 60_000.0,
+            "h" => 3_600_000.0,
+            "d" => 86_400_000.0,
+            _ => return None,
+        };
+        millis += number.parse::<f64>().ok()? * scale;
+    }
+    (!value.trim().is_empty()).then_some(millis as u64)
+}
+
+/// Reads a size in bytes, either a number or `1.5 GB` in binary units.
+fn parse_memory(value: &str) -> Option<u64> {
+    let (number, unit) = value.split_once(' ').unwrap_or((value, "B"));
+    let scale: u64 = match unit.trim() {
+        "B" => 1,
+        "KB" => 1 << 10,
+        "MB" => 1 << 20,
+        "GB" => 1 << 30,
+        "TB" => 1 << 40,
+        "PB" => 1 << 50,
+        _ => return None,
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .map(|number| (number * scale as f64) as u64)
+}
+
+/// Reads a CPU percentage such as `350.0%`.
+fn parse_percent(value: &str) -> Option<f64> {
+    value.trim_end_matches('%').parse().ok()
+}
+
+fn is_trace_name(name: &std::ffi::OsStr) -> bool {
+    let name = name.to_string_lossy();
+    name.ends_with(".txt")
+        && (name == "trace.txt"
+            || name.starts_with("execution_trace")
+            || name.starts_with("trace-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{find_run_trace, parse_duration, parse_memory, parse_trace, progress_from_trace};
+    use crate::{nextflow::nextflow_log_item::NxfLogItem, tempdir::unique_test_dir};
+
+    const TRACE: &str = "\
+task_id\thash\tnative_id\tname\tstatus\texit
+1\tab/cdef12\t101\talignment:minimap2 (1)\tCOMPLETED\t0
+2\tab/cdef13\t102\talignment:minimap2 (2)\tFAILED\t137
+3\tcd/ef0123\t103\talignment:minimap2 (2)\tCOMPLETED\t0
+4\tde/f01234\t104\tmakeReport\tCACHED\t0
+5\tef/012345\t105\tcheckSheet (sample_1)\tABORTED\t-
+";
+
+    #[test]
+    fn parses_rows_through_the_header() {
+        let records = parse_trace(TRACE);
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[0].process, "alignment:minimap2");
+        assert_eq!(records[1].hash, "ab/cdef13");
+        assert_eq!(records[1].exit, "137");
+        assert_eq!(records[4].process, "checkSheet");
+    }
+
+    #[test]
+    fn counts_last_attempt_per_task() {
+        let progress = progress_from_trace(&parse_trace(TRACE));
+        let keys: Vec<&str> = progress.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["alignment:minimap2", "makeReport", "checkSheet"]);
+
+        let minimap = &progress[0].1;
+        assert_eq!((minimap.total, minimap.complete, minimap.failed), (2, 2, 0));
+        assert_eq!(minimap.status, "COMPLETED");
+        assert_eq!(progress[1].1.cached, 1);
+        assert_eq!(progress[2].1.status, "FAILED");
+    }
+
+    #[test]
+    fn takes_only_the_trace_of_the_run() {
+        let tmp = unique_test_dir("trace");
+        let root = tmp.path.clone();
+        fs::create_dir_all(root.join("execution")).unwrap();
+        fs::write(
+            root.join("execution/trace-20261018-1.txt"),
+            "hash\tname\tstatus\nab/cdef12\tmakeReport\tCOMPLETED\n",
+        )
+        .unwrap();
+        // written later by another run publishing into the same folder
+        fs::write(
+            root.join("execution/trace.txt"),
+            "hash\tname\tstatus\nff/000000\tmakeReport\tFAILED\n",
+        )
+        .unwrap();
+
+        let run = NxfLogItem {
+            run_name: String::from("clever_ampere"),
+            ..Default::default()
+        };
+        let log = "Oct-18 10:00:04.000 [Task submitter] INFO  nextflow.Session - [ab/cdef12] Submitted process > makeReport";
+        let records = find_run_trace(&root, &run, log).unwrap();
+        assert_eq!(records[0].status, "COMPLETED");
+        assert!(find_run_trace(&root, &run, "").is_none());
+    }
+
+    #[test]
+    fn reads_formatted_and_raw_resource_columns() {
+        let records = parse_trace(
+            "\
+name\tstatus\tsubmit\trealtime\t%cpu\tpeak_rss\twchar
+minimap2 (1)\tCOMPLETED\t2026-10-18 10:00:04.000\t1m 3.5s\t350.0%\t1.5 GB\t0
+minimap2 (2)\tCOMPLETED\t1792317604000\t63500\t350.0\t1610612736\t-
+",
+        );
+        for record in &records {
+            assert_eq!(record.submit, Some(1_792_317_604_000));
+            assert_eq!(record.realtime, Some(63_500));
+            assert_eq!(record.cpu_percent, Some(350.0));
+            assert_eq!(record.peak_rss, Some(1_610_612_736));
+        }
+        assert_eq!(records[0].wchar, Some(0));
+        assert_eq!(records[1].wchar, None);
+    }
+
+    #[test]
+    fn parses_durations_and_sizes() {
+        assert_eq!(parse_duration("1h 2m 3s"), Some(3_723_000));
+        assert_eq!(parse_duration("350ms"), Some(350));
+        assert_eq!(parse_duration("-"), None);
+        assert_eq!(parse_memory("900 MB"), Some(900 << 20));
+        assert_eq!(parse_memory("12 KB"), Some(12 << 10));
+        assert_eq!(parse_memory("0"), Some(0));
+        assert_eq!(parse_memory("-"), None);
+    }
+
+    #[test]
+    fn ignores_traces_without_required_columns() {
+        assert!(parse_trace("task_id\thash\n1\tab/cdef12\n").is_empty());
+        assert!(parse_trace("").is_empty());
+    }
+}
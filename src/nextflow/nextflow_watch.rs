@@ -0,0 +1,227 @@
+//! Unattended bundling of runs as they finish in a shared folder.
+//!
+//! During a course every trainee run in a shared launch folder should become a
+//! `.2me` archive without anyone running `nextflow-run` by hand. A watcher
+//! rescans the folder's history, bundles each newly finished run and records
+//! it in a state file next to the archives, so that a restarted watcher does
+//! not bundle the same runs again.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    epi2me_db::Epi2meSetup, epi4you_errors::Epi4youError, tempdir, xmanifest::Epi2MeManifest,
+};
+
+use super::{
+    nextflow_log_item::NxfLogItem,
+    nextflow_toolkit::{BundleOptions, NextFlowResultFolder},
+};
+
+/// Name of the state file kept in the destination folder.
+pub const WATCH_STATE_FILE: &str = ".epi4you-watch.json";
+
+/// Runs a watcher has already bundled.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchState {
+    /// Bundled runs by run name.
+    pub bundled: BTreeMap<String, WatchedRun>,
+}
+
+/// One run bundled by a watcher.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchedRun {
+    /// Archive the run was bundled into.
+    pub archive: PathBuf,
+    /// Nextflow session UUID of the run.
+    pub session_id: String,
+    /// When the archive was written.
+    pub bundled_at: String,
+}
+
+impl WatchState {
+    /// Reads the state file at `path`; a missing file is an empty state.
+    pub fn load(path: &Path) -> Result<Self, Epi4youError> {
+        if !path.exists() {
+            return Ok(WatchState::default());
+        }
+        let content = fs::read_to_string(path)
+            .map_err(|err| Epi4youError::FailedToReadPath(path.to_path_buf(), err.into()))?;
+        serde_json::from_str(&content)
+            .map_err(|err| Epi4youError::FailedToParseFileContent(err.into()))
+    }
+
+    /// Writes the state file, replacing it only once fully written.
+    pub fn save(&self, path: &Path) -> Result<(), Epi4youError> {
+        let partial = path.with_extension("json.partial");
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|err| Epi4youError::FailedToParseFileContent(err.into()))?;
+        fs::write(&partial, content)
+            .map_err(|err| Epi4youError::FailedToWritePath(partial.clone(), err.into()))?;
+        fs::rename(&partial, path)
+            .map_err(|err| Epi4youError::FailedToWritePath(path.to_path_buf(), err.into()))
+    }
+
+    /// Returns the finished runs of `runs` that have not been bundled yet.
+    ///
+    /// Runs still going, whose status is not yet recorded, are never due.
+    /// Failed runs are only due with `include_failed`.
+    pub fn due<'a>(&self, runs: &'a [NxfLogItem], include_failed: bool) -> Vec<&'a NxfLogItem> {
+        runs.iter()
+            .filter(|run| run.is_ok() || (include_failed && run.status.trim() == "ERR"))
+            .filter(|run| !self.bundled.contains_key(run.run_name.trim()))
+            .collect()
+    }
+}
+
+/// Bundles the runs that finish in one launch folder into another folder.
+pub struct RunWatcher {
+    folder: PathBuf,
+    nxf_bin: Option<String>,
+    epi2me: Option<Epi2meSetup>,
+    out_dir: PathBuf,
+    state_path: PathBuf,
+    state: WatchState,
+}
+
+impl RunWatcher {
+    /// Prepares to watch `folder`, creating `out_dir` and reading its state.
+    ///
+    /// Both folders are made absolute so the paths recorded in the state file
+    /// and manifests do not depend on where the watcher was started. Runs are
+    /// staged in the installation described by `epi2me`, if any.
+    pub fn init(
+        folder: PathBuf,
+        nxf_bin: Option<String>,
+        epi2me: Option<Epi2meSetup>,
+        out_dir: PathBuf,
+    ) -> Result<Self, Epi4youError> {
+        let folder =
+            fs::canonicalize(&folder).map_err(|_| Epi4youError::RequiredPathMissing(folder))?;
+        if !folder.is_dir() {
+            return Err(Epi4youError::FileFoundWhenFolderExpected(folder));
+        }
+        fs::create_dir_all(&out_dir)
+            .map_err(|err| Epi4youError::FailedToCreateFolder(out_dir.clone(), err.into()))?;
+        let out_dir = fs::canonicalize(&out_dir)
+            .map_err(|err| Epi4youError::FailedToReadPath(out_dir.clone(), err.into()))?;
+        let state_path = out_dir.join(WATCH_STATE_FILE);
+        let state = WatchState::load(&state_path)?;
+        log::info!(
+            "watching [{}] - {} runs already bundled",
+            folder.display(),
+            state.bundled.len()
+        );
+        Ok(RunWatcher {
+            folder,
+            nxf_bin,
+            epi2me,
+            out_dir,
+            state_path,
+            state,
+        })
+    }
+
+    /// Rescans the history once and bundles every run that is due.
+    ///
+    /// A run that fails to bundle is logged and retried on the next scan.
+    /// Returns the name, archive and manifest of each run bundled.
+    pub fn scan(
+        &mut self,
+        options: &BundleOptions,
+    ) -> Result<Vec<(String, PathBuf, Epi2MeManifest)>, Epi4youError> {
+        let folder = NextFlowResultFolder::init(self.folder.clone(), self.nxf_bin.clone())?;
+        let mut bundled = Vec::new();
+
+        for run in self.state.due(folder.get_runs(), options.include_failed) {
+            let run_name = run.run_name.trim().to_owned();
+            let archive = self.out_dir.join(format!("{run_name}.2me.tar"));
+            log::info!("bundling finished run [{run_name}]");
+
+            let temp_dir = tempdir::get_tempdir(self.epi2me.as_ref())?;
+            let archive_str = archive.to_string_lossy();
+            match folder.bundle_cli_run(
+                self.epi2me.as_ref(),
+                &temp_dir,
+                run.clone(),
+                &archive_str,
+                options,
+            ) {
+                Ok(manifest) => {
+                    self.state.bundled.insert(
+                        run_name.clone(),
+                        WatchedRun {
+                            archive: archive.clone(),
+                            session_id: run.session_id.clone(),
+                            bundled_at: Local::now().to_string(),
+                        },
+                    );
+                    self.state.save(&self.state_path)?;
+                    bundled.push((run_name, archive, manifest));
+                }
+                Err(err) => log::warn!("unable to bundle run [{run_name}] - {err}"),
+            }
+        }
+        Ok(bundled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WatchState, WatchedRun};
+    use crate::{nextflow::nextflow_history::parse_history, tempdir::unique_test_dir};
+
+    #[test]
+    fn only_unbundled_finished_runs_are_due() {
+        let runs = parse_history(
+            "\
+2026-10-18 09:00:00\t5m\tdone_run\tOK\tabc\tsess-1\tnextflow run wf
+2026-10-18 09:30:00\t5m\tfailed_run\tERR\tabc\tsess-2\tnextflow run wf
+2026-10-18 10:00:00\t-\trunning_run\t-\tabc\tsess-3\tnextflow run wf
+2026-10-18 10:30:00\t5m\tnew_run\tOK\tabc\tsess-4\tnextflow run wf
+",
+        );
+        let mut state = WatchState::default();
+        state
+            .bundled
+            .insert(String::from("done_run"), WatchedRun::default());
+
+        let due = |include_failed| -> Vec<String> {
+            state
+                .due(&runs, include_failed)
+                .iter()
+                .map(|run| run.run_name.clone())
+                .collect()
+        };
+        assert_eq!(due(false), ["new_run"]);
+        assert_eq!(due(true), ["failed_run", "new_run"]);
+    }
+
+    #[test]
+    fn state_survives_a_restart() {
+        let tmp = unique_test_dir("watch");
+        let dir = tmp.path.clone();
+        let path = dir.join(super::WATCH_STATE_FILE);
+        assert_eq!(WatchState::load(&path).unwrap(), WatchState::default());
+
+        let mut state = WatchState::default();
+        state.bundled.insert(
+            String::from("clever_ampere"),
+            WatchedRun {
+                archive: dir.join("clever_ampere.2me.tar"),
+                session_id: String::from("sess-1"),
+                bundled_at: String::from("2026-10-18 10:05:00"),
+            },
+        );
+        state.save(&path).unwrap();
+        assert_eq!(WatchState::load(&path).unwrap(), state);
+    }
+}
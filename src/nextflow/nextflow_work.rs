@@ -0,0 +1,326 @@
+//! Selection and bundling of task work directories.
+//!
+//! Published outputs rarely explain why a task misbehaved. Its work directory
+//! holds the script that ran, what it printed and how it exited, which is what
+//! a trainer preparing a troubleshooting exercise or a support engineer asks
+//! for first. Tasks are identified by the hashes in the execution trace, or in
+//! the `.nextflow.log` when there is no trace, and only the ones asked for are
+//! copied next to the outputs.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::epi4you_errors::Epi4youError;
+
+use super::{
+    nextflow_command::NextflowCommand,
+    nextflow_timeline::{LogEvent, NextflowTimeline},
+    nextflow_trace::TraceRecord,
+};
+
+/// Folder of the staged analysis that task work directories are copied into.
+pub const BUNDLED_WORK_DIR: &str = "epi4you/work";
+/// Work folder Nextflow uses when `-w` is not given.
+const DEFAULT_WORK_DIR: &str = "work";
+/// Files Nextflow writes into every task directory that describe the task.
+const TASK_FILES: [&str; 5] = [
+    ".command.sh",
+    ".command.log",
+    ".command.out",
+    ".command.err",
+    ".exitcode",
+];
+
+/// Which task work directories to bundle, as given with `--with-work`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum WorkMode {
+    /// No work directories.
+    #[default]
+    None,
+    /// Tasks that failed or were aborted.
+    FailedTasks,
+    /// Every task of the run.
+    All,
+    /// Tasks of one process, by full or unqualified name.
+    Process(String),
+}
+
+impl FromStr for WorkMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode {
+            "none" => Ok(WorkMode::None),
+            "failed-tasks" => Ok(WorkMode::FailedTasks),
+            "all" => Ok(WorkMode::All),
+            _ => match mode.strip_prefix("process=") {
+                Some(process) if !process.is_empty() => Ok(WorkMode::Process(process.to_owned())),
+                _ => Err(format!(
+                    "expected none, failed-tasks, all or process=<name>, got [{mode}]"
+                )),
+            },
+        }
+    }
+}
+
+impl WorkMode {
+    /// Returns true if the work directory of `task` should be bundled.
+    ///
+    /// A process given without its workflow prefix matches the last part of
+    /// the qualified name, so `minimap2` selects `alignment:minimap2`.
+    pub fn selects(&self, task: &WorkTaskDetails) -> bool {
+        match self {
+            WorkMode::None => false,
+            WorkMode::FailedTasks => matches!(task.status.as_str(), "FAILED" | "ABORTED"),
+            WorkMode::All => true,
+            WorkMode::Process(process) => {
+                task.process == *process || task.process.rsplit(':').next() == Some(process)
+            }
+        }
+    }
+}
+
+/// One task whose work directory was asked for.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkTaskDetails {
+    /// Short work-directory hash such as `ab/cdef12`.
+    pub hash: String,
+    /// Task name, the process name followed by its tag or index.
+    pub name: String,
+    /// Process name.
+    pub process: String,
+    /// Last known status of the task.
+    pub status: String,
+    /// Location of the bundled copy relative to the analysis folder.
+    pub bundled: Option<String>,
+}
+
+/// Lists the task attempts of a run, in the order they were started.
+///
+/// The trace is preferred as it records every attempt with its final status.
+/// Without one the submitted, cached and finished tasks in the log are used.
+pub fn run_tasks(
+    trace: Option<&[TraceRecord]>,
+    timeline: &NextflowTimeline,
+) -> Vec<WorkTaskDetails> {
+    if let Some(records) = trace {
+        return records
+            .iter()
+            .filter(|record| !record.hash.is_empty())
+            .map(|record| WorkTaskDetails {
+                hash: record.hash.clone(),
+                name: record.name.clone(),
+                process: record.process.clone(),
+                status: record.status.clone(),
+                bundled: None,
+            })
+            .collect();
+    }
+
+    let mut tasks: Vec<WorkTaskDetails> = Vec::new();
+    for entry in &timeline.events {
+        let (hash, name, process, status) = match &entry.event {
+            LogEvent::TaskSubmitted {
+                hash,
+                name,
+                process,
+            } => (hash, name, process, "SUBMITTED"),
+            LogEvent::TaskCached {
+                hash,
+                name,
+                process,
+            } => (hash, name, process, "CACHED"),
+            LogEvent::TaskCompleted {
+                hash,
+                name,
+                process,
+                ..
+            } => (hash, name, process, "COMPLETED"),
+            LogEvent::TaskFailed {
+                hash,
+                name,
+                process,
+                ..
+            } => (hash, name, process, "FAILED"),
+            _ => continue,
+        };
+        if hash.is_empty() {
+            continue;
+        }
+        match tasks.iter_mut().find(|task| task.hash == *hash) {
+            Some(task) => task.status = status.to_owned(),
+            None => tasks.push(WorkTaskDetails {
+                hash: hash.clone(),
+                name: name.clone(),
+                process: process.clone(),
+                status: status.to_owned(),
+                bundled: None,
+            }),
+        }
+    }
+    tasks
+}
+
+/// Returns the work folder of a run launched from `launch_dir`.
+pub fn work_dir(command: &NextflowCommand, launch_dir: &Path) -> PathBuf {
+    let work_dir = command
+        .option("-w")
+        .or_else(|| command.option("-work-dir"))
+        .unwrap_or(DEFAULT_WORK_DIR);
+    launch_dir.join(work_dir)
+}
+
+/// Copies the work directories of `tasks` into the staged analysis.
+///
+/// The task description files are always copied; the files a task produced
+/// only with `include_outputs`. Staged inputs are symlinks and never copied.
+/// A task whose directory has been cleaned up is recorded without a copy.
+pub fn bundle_work_dirs(
+    tasks: &mut [WorkTaskDetails],
+    work_dir: &Path,
+    staging: &Path,
+    include_outputs: bool,
+) -> Result<(), Epi4youError> {
+    for task in tasks.iter_mut() {
+        let Some(task_dir) = find_task_dir(work_dir, &task.hash) else {
+            log::warn!(
+                "work directory of task [{}] {} not found",
+                task.hash,
+                task.name
+            );
+            continue;
+        };
+        let relative = task_dir.strip_prefix(work_dir).unwrap_or(&task_dir);
+        let bundled = PathBuf::from(BUNDLED_WORK_DIR).join(relative);
+        let target_dir = staging.join(&bundled);
+
+        for entry in WalkDir::new(&task_dir).min_depth(1).into_iter().flatten() {
+            let Ok(path) = entry.path().strip_prefix(&task_dir) else {
+                continue;
+            };
+            let hidden = path
+                .components()
+                .next()
+                .is_some_and(|first| first.as_os_str().to_string_lossy().starts_with('.'));
+            let wanted = if hidden {
+                TASK_FILES.iter().any(|name| path == Path::new(name))
+            } else {
+                include_outputs
+            };
+            if !wanted || !entry.file_type().is_file() {
+                continue;
+            }
+
+            let target = target_dir.join(path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent).map_err(|err| {
+                    Epi4youError::FailedToCreateFolder(parent.to_path_buf(), err.into())
+                })?;
+            }
+            fs::copy(entry.path(), &target)
+                .map_err(|err| Epi4youError::FailedToWritePath(target.clone(), err.into()))?;
+        }
+        log::info!("bundling work directory [{}]", task_dir.display());
+        task.bundled = Some(bundled.to_string_lossy().into_owned());
+    }
+    Ok(())
+}
+
+/// Expands a short `ab/cdef12` hash to the task directory below `work_dir`.
+fn find_task_dir(work_dir: &Path, hash: &str) -> Option<PathBuf> {
+    let (prefix, short) = hash.split_once('/')?;
+    fs::read_dir(work_dir.join(prefix))
+        .ok()?
+        .flatten()
+        .find(|entry| {
+            entry.file_name().to_string_lossy().starts_with(short)
+                && entry.file_type().is_ok_and(|kind| kind.is_dir())
+        })
+        .map(|entry| entry.path())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{bundle_work_dirs, run_tasks, WorkMode};
+    use crate::{
+        nextflow::{nextflow_timeline::NextflowTimeline, nextflow_trace::parse_trace},
+        tempdir::unique_test_dir,
+    };
+
+    const TRACE: &str = "\
+task_id\thash\tnative_id\tname\tstatus\texit
+1\tab/cdef12\t101\talignment:minimap2 (1)\tCOMPLETED\t0
+2\tab/cdef13\t102\talignment:minimap2 (2)\tFAILED\t137
+3\tcd/ef0123\t103\tmakeReport\tCOMPLETED\t0
+";
+
+    #[test]
+    fn parses_modes_and_selects_tasks() {
+        assert_eq!("none".parse(), Ok(WorkMode::None));
+        assert_eq!("all".parse(), Ok(WorkMode::All));
+        assert!("process=".parse::<WorkMode>().is_err());
+        assert!("some".parse::<WorkMode>().is_err());
+
+        let tasks = run_tasks(Some(&parse_trace(TRACE)), &NextflowTimeline::default());
+        let selected = |mode: &str| -> Vec<String> {
+            let mode: WorkMode = mode.parse().unwrap();
+            tasks
+                .iter()
+                .filter(|task| mode.selects(task))
+                .map(|task| task.hash.clone())
+                .collect()
+        };
+        assert_eq!(selected("failed-tasks"), ["ab/cdef13"]);
+        assert_eq!(selected("process=minimap2"), ["ab/cdef12", "ab/cdef13"]);
+        assert_eq!(selected("process=makeReport"), ["cd/ef0123"]);
+        assert_eq!(selected("all").len(), 3);
+    }
+
+    #[test]
+    fn falls_back_to_the_log_for_task_status() {
+        let log = "\
+Oct-18 11:00:04.000 [Task submitter] INFO  nextflow.Session - [ef/012345] Submitted process > alignment:minimap2 (1)
+Oct-18 11:00:10.000 [Task monitor] DEBUG nextflow.processor.TaskProcessor - Task completed > TaskHandler[id: 1; name: alignment:minimap2 (1); status: COMPLETED; exit: 1; error: -; workDir: /runs/work/ef/0123456789abcdef]
+";
+        let tasks = run_tasks(None, &NextflowTimeline::parse(log));
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].status, "FAILED");
+    }
+
+    #[test]
+    fn copies_task_files_and_optionally_outputs() {
+        let tmp = unique_test_dir("work");
+        let root = tmp.path.clone();
+        let task_dir = root.join("work/ab/cdef13456789");
+        fs::create_dir_all(&task_dir).unwrap();
+        fs::write(task_dir.join(".command.sh"), "minimap2 ...").unwrap();
+        fs::write(task_dir.join(".command.run"), "#!/bin/bash").unwrap();
+        fs::write(task_dir.join("aligned.bam"), "bam").unwrap();
+
+        let mut tasks = run_tasks(Some(&parse_trace(TRACE)), &NextflowTimeline::default());
+        let staging = root.join("staging");
+        bundle_work_dirs(&mut tasks[..2], &root.join("work"), &staging, false).unwrap();
+
+        assert_eq!(tasks[0].bundled, None);
+        assert_eq!(
+            tasks[1].bundled.as_deref(),
+            Some("epi4you/work/ab/cdef13456789")
+        );
+        let bundled = staging.join(PathBuf::from(tasks[1].bundled.as_ref().unwrap()));
+        assert!(bundled.join(".command.sh").is_file());
+        assert!(!bundled.join(".command.run").exists());
+        assert!(!bundled.join("aligned.bam").exists());
+
+        bundle_work_dirs(&mut tasks[1..2], &root.join("work"), &staging, true).unwrap();
+        assert!(bundled.join("aligned.bam").is_file());
+    }
+}
@@ -6,9 +6,12 @@
 //! do not have the Desktop database record, so we recover that information from
 //! the preserved Nextflow output instead.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path};
 
-use url::{Position, Url};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::nextflow::nextflow_timeline::{LogEvent, NextflowTimeline};
 
 /// Default workflow version when the log does not expose one clearly.
 const DEFAULT_VERSION: &str = "dev";
@@ -17,7 +20,22 @@ const LAUNCH_PREFIX: &str = "Launching `";
 /// Text preceding the workflow revision token.
 const REVISION_KEY: &str = " - revision: ";
 /// Sentinel found in lines that expose version text in preserved stdout.
-const VERSION_MARKER: &str = "||||||||||";
+pub(crate) const VERSION_MARKER: &str = "||||||||||";
+/// Project assumed for a bare workflow name, as Desktop installs them.
+const DEFAULT_PROJECT: &str = "epi2me-labs";
+/// Project recorded for workflows run from a local script or folder.
+const LOCAL_PROJECT: &str = "local";
+/// Host Nextflow pulls `owner/repo` pipelines from unless `-hub` says otherwise.
+const DEFAULT_HOST: &str = "github.com";
+/// Hosts of the providers `-hub` accepts by name.
+const HUB_HOSTS: [(&str, &str); 4] = [
+    ("github", "github.com"),
+    ("gitlab", "gitlab.com"),
+    ("bitbucket", "bitbucket.org"),
+    ("gitea", "gitea.com"),
+];
+/// Script Nextflow runs when given a folder.
+const MAIN_SCRIPT: &str = "main.nf";
 
 /// Small metadata bag extracted from a Nextflow launch transcript.
 ///
@@ -28,29 +46,34 @@ pub struct NextFlowLogs {
 }
 
 impl NextFlowLogs {
-    /// Parses a reduced `nextflow.stdout` transcript into named metadata values.
+    /// Parses a Nextflow log or reduced `nextflow.stdout` transcript into
+    /// named metadata values.
     pub fn init(log: &str) -> Self {
-        let mut facet = HashMap::<String, String>::new();
+        Self::from_timeline(&NextflowTimeline::parse(log))
+    }
 
-        let mut name = String::new();
-        let mut revision = String::new();
-        let mut project = String::new();
-        let mut pname = String::new();
-        let mut version = String::from(DEFAULT_VERSION);
-
-        for line in log.lines() {
-            if let Some(parsed) = parse_launch_line(line) {
-                name = parsed.name;
-                revision = parsed.revision;
-                project = parsed.project;
-                pname = parsed.pname;
-                continue;
-            }
+    /// Derives the metadata values from an already parsed timeline.
+    pub fn from_timeline(timeline: &NextflowTimeline) -> Self {
+        let mut facet = HashMap::<String, String>::new();
 
-            if !pname.is_empty() && line.contains(VERSION_MARKER) && line.contains(&pname) {
-                version = parse_version(line, &pname);
-            }
-        }
+        let (name, revision, project, pname) = match timeline.launch() {
+            Some(LogEvent::Launch {
+                run_name,
+                project,
+                workflow,
+                revision,
+            }) => (
+                run_name.clone(),
+                revision.clone(),
+                project.clone(),
+                workflow.clone(),
+            ),
+            _ => Default::default(),
+        };
+        let version = timeline
+            .workflow_version()
+            .unwrap_or(DEFAULT_VERSION)
+            .to_owned();
 
         facet.insert("name".into(), name);
         facet.insert("revision".into(), revision);
@@ -76,15 +99,15 @@ impl NextFlowLogs {
 
 /// Structured representation of one launch line.
 #[derive(Debug, PartialEq, Eq)]
-struct LaunchLine {
-    name: String,
-    revision: String,
-    project: String,
-    pname: String,
+pub(crate) struct LaunchLine {
+    pub(crate) name: String,
+    pub(crate) revision: String,
+    pub(crate) project: String,
+    pub(crate) pname: String,
 }
 
 /// Parses the Nextflow "Launching ..." line into its core identifiers.
-fn parse_launch_line(line: &str) -> Option<LaunchLine> {
+pub(crate) fn parse_launch_line(line: &str) -> Option<LaunchLine> {
     let clipped_line = line.split_once(LAUNCH_PREFIX)?.1;
     let url_str = clipped_line.split('`').next()?;
     let name = clipped_line
@@ -112,25 +135,118 @@ fn parse_launch_line(line: &str) -> Option<LaunchLine> {
     })
 }
 
-/// Extracts `{project, workflow}` from either a full URL or a shorthand tuple.
+/// Extracts `{project, workflow}` from the pipeline named in a launch line.
 ///
-/// EPI2ME workflows are commonly referenced as GitHub paths such as
-/// `epi2me-labs/wf-human-variation`, but some logs collapse this to a shorter
-/// workflow-only form. The fallback keeps `epi4you` useful in both cases.
+/// See [`WorkflowSource::parse`] for the forms understood.
 fn parse_project_and_name(url_str: &str) -> (String, String) {
-    if let Ok(url) = Url::parse(url_str) {
-        let data_url_payload = &url[Position::AfterHost..].trim_start_matches('/');
-        if let Some((project, pname)) = data_url_payload.split_once('/') {
-            return (project.to_string(), pname.to_string());
+    let source = WorkflowSource::parse(url_str, None);
+    (source.project, source.name)
+}
+
+/// Where the workflow of a CLI run came from.
+///
+/// Desktop only knows workflows it installed itself from GitHub, so runs of
+/// local scripts or of repositories on other hosts are imported as analyses
+/// Desktop cannot relaunch; [`WorkflowSource::recognised_by_desktop`] says so.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowSource {
+    /// Host the repository was pulled from; `None` for a local script.
+    pub host: Option<String>,
+    /// Owner or group path, such as `epi2me-labs` or `group/subgroup`.
+    pub project: String,
+    /// Repository name, or the folder or script name of a local workflow.
+    pub name: String,
+    /// Script or folder as given, for a local workflow.
+    pub script: Option<String>,
+    /// Whether Desktop can match the run to a workflow it installs.
+    pub recognised_by_desktop: bool,
+}
+
+impl WorkflowSource {
+    /// Identifies the workflow from the pipeline given to `nextflow run`.
+    ///
+    /// Understood are repository URLs on any host, including nested GitLab
+    /// groups, `owner/repo` shorthands resolved against `hub` (the `-hub`
+    /// option), bare EPI2ME workflow names, and local folders and `.nf`
+    /// scripts. A local `main.nf` is named after the folder holding it.
+    pub fn parse(pipeline: &str, hub: Option<&str>) -> Self {
+        let pipeline = pipeline.trim();
+        if is_local(pipeline) {
+            let path = Path::new(pipeline);
+            let name = match path.extension() {
+                Some(ext) if ext == "nf" && path.file_name() == Some(MAIN_SCRIPT.as_ref()) => {
+                    path.parent().and_then(Path::file_name)
+                }
+                Some(ext) if ext == "nf" => path.file_stem(),
+                _ => path.file_name(),
+            }
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+            return WorkflowSource {
+                host: None,
+                project: LOCAL_PROJECT.to_owned(),
+                name,
+                script: Some(pipeline.to_owned()),
+                recognised_by_desktop: false,
+            };
+        }
+
+        let (host, path) = match Url::parse(pipeline) {
+            Ok(url) if url.has_host() => (
+                url.host_str().unwrap_or_default().to_owned(),
+                url.path().to_owned(),
+            ),
+            _ => match pipeline.split_once('/') {
+                Some((host, path)) if host.contains('.') => (host.to_owned(), path.to_owned()),
+                _ => (hub_host(hub), pipeline.to_owned()),
+            },
+        };
+        let path = path.trim_matches('/').trim_end_matches(".git");
+        let (project, name) = match path.rsplit_once('/') {
+            Some((project, name)) => (project.to_owned(), name.to_owned()),
+            None => (DEFAULT_PROJECT.to_owned(), path.to_owned()),
+        };
+        WorkflowSource {
+            recognised_by_desktop: host == DEFAULT_HOST && !project.contains('/'),
+            host: Some(host),
+            project,
+            name,
+            script: None,
+        }
+    }
+
+    /// Names a local workflow after `launch_dir` when it was run as `main.nf`
+    /// or `.` from there.
+    pub fn with_launch_dir(mut self, launch_dir: &Path) -> Self {
+        if self.script.is_some() && self.name.is_empty() {
+            self.name = launch_dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
         }
+        self
     }
+}
+
+/// Returns true if the pipeline names a path rather than a repository.
+fn is_local(pipeline: &str) -> bool {
+    pipeline.starts_with(['/', '.', '~']) || pipeline.ends_with(".nf")
+}
 
-    let fallback_name = url_str.split('/').next().unwrap_or_default().to_string();
-    ("epi2me-labs".to_string(), fallback_name)
+fn hub_host(hub: Option<&str>) -> String {
+    match hub {
+        Some(hub) => HUB_HOSTS
+            .iter()
+            .find(|(name, _)| *name == hub)
+            .map_or(hub, |(_, host)| host)
+            .to_owned(),
+        None => DEFAULT_HOST.to_owned(),
+    }
 }
 
 /// Extracts a simplified workflow version token from a log line.
-fn parse_version(line: &str, pname: &str) -> String {
+pub(crate) fn parse_version(line: &str, pname: &str) -> String {
     line.split_once(pname)
         .map(|(_, suffix)| suffix.trim())
         .and_then(|suffix| suffix.split('-').next())
@@ -142,7 +258,9 @@ fn parse_version(line: &str, pname: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_launch_line, parse_version, NextFlowLogs};
+    use std::path::Path;
+
+    use super::{parse_launch_line, parse_version, NextFlowLogs, WorkflowSource};
 
     #[test]
     fn parses_launch_line_with_url() {
@@ -166,6 +284,40 @@ mod tests {
         assert_eq!(parsed.pname, "wf-basecalling");
     }
 
+    #[test]
+    fn identifies_hosted_workflows() {
+        let source = WorkflowSource::parse("https://gitlab.com/lab/pipelines/wf-qc.git", None);
+        assert_eq!(source.host.as_deref(), Some("gitlab.com"));
+        assert_eq!(
+            (source.project.as_str(), source.name.as_str()),
+            ("lab/pipelines", "wf-qc")
+        );
+        assert!(!source.recognised_by_desktop);
+
+        let source = WorkflowSource::parse("team/wf-qc", Some("bitbucket"));
+        assert_eq!(source.host.as_deref(), Some("bitbucket.org"));
+        assert_eq!(source.project, "team");
+
+        let source = WorkflowSource::parse("epi2me-labs/wf-alignment", None);
+        assert_eq!(source.host.as_deref(), Some("github.com"));
+        assert!(source.recognised_by_desktop);
+    }
+
+    #[test]
+    fn identifies_local_workflows() {
+        let named = |pipeline: &str| WorkflowSource::parse(pipeline, None).name;
+        assert_eq!(named("./wf-custom/main.nf"), "wf-custom");
+        assert_eq!(named("/opt/pipelines/wf-custom"), "wf-custom");
+        assert_eq!(named("/opt/pipelines/analysis.nf"), "analysis");
+        assert_eq!(named("main.nf"), "");
+
+        let source = WorkflowSource::parse("./", None).with_launch_dir(Path::new("/runs/wf-mine"));
+        assert_eq!(source.project, "local");
+        assert_eq!(source.name, "wf-mine");
+        assert_eq!(source.script.as_deref(), Some("./"));
+        assert!(!source.recognised_by_desktop);
+    }
+
     #[test]
     fn parses_version_without_dash_suffix() {
         let version = parse_version("|||||||||| wf-basecalling 1.0.0", "wf-basecalling");
@@ -0,0 +1,190 @@
+//! Rendering of command results for people and for scripts.
+//!
+//! Provisioning scripts need something more stable than Polars' table
+//! formatting, so every command hands its result to [`emit`] and the global
+//! `--output` flag decides how it is written. Results always go to stdout;
+//! progress and diagnostics go through the logger to stderr.
+
+use std::{error::Error, sync::OnceLock};
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use epi4you::Epi4youError;
+
+/// Names accepted by `--output`.
+pub const OUTPUT_FORMATS: [&str; 3] = ["table", "json", "tsv"];
+
+/// Supported renderings of command results.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-oriented aligned text.
+    Table,
+    /// One JSON document per command.
+    Json,
+    /// Tab-separated values with a header row.
+    Tsv,
+}
+
+impl OutputFormat {
+    /// Parses one of [`OUTPUT_FORMATS`].
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "table" => Some(OutputFormat::Table),
+            "json" => Some(OutputFormat::Json),
+            "tsv" => Some(OutputFormat::Tsv),
+            _ => None,
+        }
+    }
+}
+
+/// Output format selected on the command line, if any.
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// Selects the output format. Only the first call has any effect.
+pub fn set_output_format(format: OutputFormat) {
+    let _ = OUTPUT_FORMAT.set(format);
+}
+
+/// Returns the selected output format, defaulting to [`OutputFormat::Table`].
+pub fn get_output_format() -> OutputFormat {
+    *OUTPUT_FORMAT.get().unwrap_or(&OutputFormat::Table)
+}
+
+/// Writes a command result to stdout in the selected format.
+///
+/// Results are expected to serialize to an object (one record) or an array of
+/// objects (many records); anything else is printed as a single value.
+pub fn emit<T: Serialize>(result: &T) {
+    let value = serde_json::to_value(result).unwrap_or(Value::Null);
+    match get_output_format() {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&value).unwrap_or_default()
+        ),
+        OutputFormat::Tsv => print_tsv(&as_records(&value)),
+        OutputFormat::Table => print_table(&value),
+    }
+}
+
+/// Writes a failed command's error to stdout when JSON output is selected.
+///
+/// Returns false when the error should instead be reported on stderr.
+pub fn emit_error(err: &Epi4youError) -> bool {
+    if get_output_format() != OutputFormat::Json {
+        return false;
+    }
+    let mut causes = Vec::new();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+    emit(&json!({
+        "error": err,
+        "message": err.to_string(),
+        "causes": causes,
+        "category": err.category(),
+        "exit_code": err.exit_code(),
+    }));
+    true
+}
+
+/// Normalises a result into a list of flat records.
+fn as_records(value: &Value) -> Vec<Map<String, Value>> {
+    match value {
+        Value::Array(items) => items.iter().flat_map(as_records).collect(),
+        Value::Object(record) => vec![record.clone()],
+        Value::Null => Vec::new(),
+        other => {
+            let mut record = Map::new();
+            record.insert(String::from("value"), other.clone());
+            vec![record]
+        }
+    }
+}
+
+/// Renders a scalar cell; nested structures are written as compact JSON.
+fn cell(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn print_tsv(records: &[Map<String, Value>]) {
+    let Some(first) = records.first() else {
+        return;
+    };
+    let header: Vec<&String> = first.keys().collect();
+    println!(
+        "{}",
+        header
+            .iter()
+            .map(|key| key.as_str())
+            .collect::<Vec<_>>()
+            .join("\t")
+    );
+    for record in records {
+        let row: Vec<String> = header
+            .iter()
+            .map(|key| cell(record.get(*key).unwrap_or(&Value::Null)).replace(['\t', '\n'], " "))
+            .collect();
+        println!("{}", row.join("\t"));
+    }
+}
+
+fn print_table(value: &Value) {
+    match value {
+        Value::Object(record) => {
+            let width = record.keys().map(String::len).max().unwrap_or(0);
+            for (key, value) in record {
+                println!("{key:width$}  {}", cell(value));
+            }
+        }
+        Value::Array(_) => {
+            let records = as_records(value);
+            let Some(first) = records.first() else {
+                return;
+            };
+            let header: Vec<&String> = first.keys().collect();
+            let rows: Vec<Vec<String>> = records
+                .iter()
+                .map(|record| {
+                    header
+                        .iter()
+                        .map(|key| cell(record.get(*key).unwrap_or(&Value::Null)))
+                        .collect()
+                })
+                .collect();
+            let widths: Vec<usize> = header
+                .iter()
+                .enumerate()
+                .map(|(idx, key)| {
+                    rows.iter()
+                        .map(|row| row[idx].len())
+                        .chain([key.len()])
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+
+            let line = |cells: Vec<&str>| {
+                cells
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{cell:width$}"))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            };
+            println!("{}", line(header.iter().map(|key| key.as_str()).collect()));
+            for row in &rows {
+                println!("{}", line(row.iter().map(String::as_str).collect()));
+            }
+        }
+        other => println!("{}", cell(other)),
+    }
+}
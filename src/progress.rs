@@ -0,0 +1,171 @@
+//! Byte-based progress reporting for long-running file operations.
+//!
+//! Hashing, archiving and copying a large analysis can take many minutes. Once
+//! [`set_draw_bars`] allows it and stdout is a terminal, a progress bar with
+//! throughput and ETA is drawn on stderr; otherwise a summary line is logged
+//! every few seconds so that CI logs, redirected output and library callers
+//! are not filled with bar redraws.
+
+use std::{
+    io::{self, IsTerminal, Read},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use indicatif::{HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressStyle};
+
+/// How often a progress line is logged when no bar is drawn.
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+/// Bar layout: label, bar, bytes, throughput and ETA.
+const BAR_TEMPLATE: &str =
+    "{msg:12} [{bar:30}] {bytes}/{total_bytes} {binary_bytes_per_sec} ETA {eta}";
+
+/// Container all bars are drawn in, shared with the logger.
+static MULTI_PROGRESS: OnceLock<MultiProgress> = OnceLock::new();
+/// Whether bars may be drawn at all; unset means no.
+static DRAW_BARS: OnceLock<bool> = OnceLock::new();
+
+/// Allows progress bars on a terminal instead of log lines alone.
+///
+/// Library callers that never call this only see progress through the `log`
+/// facade. Only the first call has any effect.
+pub fn set_draw_bars(draw: bool) {
+    let _ = DRAW_BARS.set(draw);
+}
+
+/// Returns the container progress bars are drawn in.
+///
+/// A logger that writes to stderr should print through
+/// [`MultiProgress::suspend`] so that log lines do not tear an active bar.
+pub fn multi_progress() -> &'static MultiProgress {
+    MULTI_PROGRESS.get_or_init(MultiProgress::new)
+}
+
+/// Progress of one operation over a known number of bytes.
+pub struct Progress {
+    bar: Option<ProgressBar>,
+    label: String,
+    total: u64,
+    done: AtomicU64,
+    started: Instant,
+    last_logged: Mutex<Instant>,
+}
+
+impl Progress {
+    /// Starts reporting an operation that will process `total` bytes.
+    pub fn new(label: &str, total: u64) -> Self {
+        let draw = *DRAW_BARS.get().unwrap_or(&false) && io::stdout().is_terminal();
+        let bar = draw.then(|| {
+            let bar = multi_progress().add(ProgressBar::new(total));
+            if let Ok(style) = ProgressStyle::with_template(BAR_TEMPLATE) {
+                bar.set_style(style.progress_chars("=> "));
+            }
+            bar.set_message(label.to_owned());
+            bar
+        });
+        if bar.is_none() {
+            log::info!("{label}: {} to process", HumanBytes(total));
+        }
+
+        let now = Instant::now();
+        Progress {
+            bar,
+            label: label.to_owned(),
+            total,
+            done: AtomicU64::new(0),
+            started: now,
+            last_logged: Mutex::new(now),
+        }
+    }
+
+    /// Records that `bytes` more bytes have been processed.
+    pub fn inc(&self, bytes: u64) {
+        let done = self.done.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if let Some(bar) = &self.bar {
+            bar.inc(bytes);
+            return;
+        }
+
+        if let Ok(mut last_logged) = self.last_logged.lock() {
+            if last_logged.elapsed() >= LOG_INTERVAL {
+                *last_logged = Instant::now();
+                self.log_line(done);
+            }
+        }
+    }
+
+    /// Wraps a reader so that bytes read through it count as progress.
+    pub fn wrap_read<R: Read>(&self, inner: R) -> ProgressRead<'_, R> {
+        ProgressRead {
+            inner,
+            progress: self,
+        }
+    }
+
+    /// Marks the operation as complete.
+    pub fn finish(&self) {
+        let done = self.done.load(Ordering::Relaxed);
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+        log::info!(
+            "{}: {} in {}",
+            self.label,
+            HumanBytes(done),
+            HumanDuration(self.started.elapsed())
+        );
+    }
+
+    fn log_line(&self, done: u64) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            done as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta = if rate > 0.0 {
+            Duration::from_secs_f64(self.total.saturating_sub(done) as f64 / rate)
+        } else {
+            Duration::ZERO
+        };
+        let percent = done
+            .saturating_mul(100)
+            .checked_div(self.total)
+            .unwrap_or(100);
+        log::info!(
+            "{}: {percent}% ({} of {}) at {}/s, ETA {}",
+            self.label,
+            HumanBytes(done),
+            HumanBytes(self.total),
+            HumanBytes(rate as u64),
+            HumanDuration(eta)
+        );
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(bar) = &self.bar {
+            if !bar.is_finished() {
+                bar.finish_and_clear();
+            }
+        }
+    }
+}
+
+/// Reader adapter returned by [`Progress::wrap_read`].
+pub struct ProgressRead<'a, R> {
+    inner: R,
+    progress: &'a Progress,
+}
+
+impl<R: Read> Read for ProgressRead<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.progress.inc(count as u64);
+        Ok(count)
+    }
+}
@@ -6,35 +6,44 @@ use std::{
 
 use ulid::Ulid;
 
-use crate::{epi2me_db::find_db, epi4you_errors::Epi4youError};
+use crate::{epi2me_db::Epi2meSetup, epi4you_errors::Epi4youError};
 
-pub fn form_tempdir(temp_path: PathBuf) -> Option<TempDir> {
+pub fn form_tempdir(temp_path: PathBuf) -> Result<TempDir, Epi4youError> {
+    create_dir_all(&temp_path)
+        .map_err(|err| Epi4youError::FailedToCreateFolder(temp_path.clone(), err.into()))?;
     let tempdir = TempDir {
-        path: PathBuf::from(&temp_path),
+        path: temp_path,
         del: true,
     };
-    let status = create_dir_all(temp_path);
-    if status.is_ok() {
-        println!("using tempdir at [{}]", &tempdir);
-        return Some(tempdir);
-    }
-    eprintln!("unable to create temporary directory ...");
-    return None;
+    log::info!("using tempdir at [{}]", &tempdir);
+    Ok(tempdir)
 }
 
-pub fn get_named_tempdir(temp_subdir: &String) -> Option<TempDir> {
-    let epi2medb = find_db();
+/// Creates `temp_subdir` in the `import_export_4you` folder of `setup`, or in
+/// the system temporary folder without one.
+pub fn get_named_tempdir(
+    setup: Option<&Epi2meSetup>,
+    temp_subdir: &String,
+) -> Result<TempDir, Epi4youError> {
     let mut epi4you_path = env::temp_dir();
-    if epi2medb.is_some() {
-        epi4you_path = epi2medb.unwrap().epi4you_path;
+    if let Some(setup) = setup {
+        epi4you_path = setup.epi4you_path.clone();
     }
     epi4you_path.push(temp_subdir);
     return form_tempdir(epi4you_path);
 }
 
-pub fn get_tempdir() -> Result<TempDir, Epi4youError> {
+pub fn get_tempdir(setup: Option<&Epi2meSetup>) -> Result<TempDir, Epi4youError> {
     let ulid_str = Ulid::new().to_string();
-    return Ok(get_named_tempdir(&ulid_str).unwrap());
+    get_named_tempdir(setup, &ulid_str)
+}
+
+/// Creates a unique folder for a unit test, removed again when dropped even
+/// if an assertion fails first.
+#[cfg(test)]
+pub(crate) fn unique_test_dir(prefix: &str) -> TempDir {
+    form_tempdir(env::temp_dir().join(format!("epi4you-{prefix}-{}", Ulid::new())))
+        .expect("unable to create test folder")
 }
 
 #[derive(Clone)]
@@ -51,13 +60,13 @@ impl TempDir {
 
 impl Drop for TempDir {
     fn drop(&mut self) {
-        let str = self.path.as_os_str().to_str().unwrap();
+        let str = self.path.to_string_lossy();
         // if has been cloned then may not exist -- test for this
         if self.path.exists() && self.del {
-            println!("Dropping TempDir with path `{}`!", str);
+            log::debug!("Dropping TempDir with path `{}`!", str);
             let cleanup = fs::remove_dir_all(&self.path);
             if cleanup.is_err() {
-                eprintln!("failed to cleanup temporary directory at [{}]", str);
+                log::error!("failed to cleanup temporary directory at [{}]", str);
             }
         }
     }
@@ -70,7 +79,7 @@ impl fmt::Display for TempDir {
         // stream: `f`. Returns `fmt::Result` which indicates whether the
         // operation succeeded or failed. Note that `write!` uses syntax which
         // is very similar to `println!`.
-        let str = self.path.as_os_str().to_str().unwrap();
+        let str = self.path.to_string_lossy();
         write!(f, "{}", str)
     }
 }
@@ -16,7 +16,7 @@
 use std::{
     fs::{self, File},
     io::{BufReader, Read},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use data_encoding::HEXUPPER;
@@ -26,8 +26,13 @@ use stringreader::StringReader;
 use tar::Archive;
 
 use crate::{
-    app_db, epi2me_desktop_analysis::Epi2meDesktopAnalysis, epi2me_workflow::Epi2meWorkflow,
-    epi4you_errors::Epi4youError, provenance::Epi2MeProvenance,
+    app_db::{self, Epi2MeAnalysis},
+    epi2me_db::Epi2meSetup,
+    epi2me_desktop_analysis::Epi2meDesktopAnalysis,
+    epi2me_workflow::Epi2meWorkflow,
+    epi4you_errors::Epi4youError,
+    progress::Progress,
+    provenance::Epi2MeProvenance,
 };
 
 /// Canonical filename used for the serialized manifest inside a `.2me` tarball.
@@ -172,8 +177,8 @@ impl Epi2MeManifest {
             return Err(Epi4youError::FolderFoundWhenFileExpected(tarball));
         }
 
-        let file =
-            File::open(&tarball).map_err(|_| Epi4youError::FailedToReadPath(tarball.clone()))?;
+        let file = File::open(&tarball)
+            .map_err(|err| Epi4youError::FailedToReadPath(tarball.clone(), err.into()))?;
         let mut archive = Archive::new(file);
         let entries = archive
             .entries()
@@ -198,10 +203,10 @@ impl Epi2MeManifest {
 
             let mut buffer = String::new();
             file.read_to_string(&mut buffer)
-                .map_err(|_| Epi4youError::FailedToReadPath(file_path.clone()))?;
+                .map_err(|err| Epi4youError::FailedToReadPath(file_path.clone(), err.into()))?;
 
             let manifest: Epi2MeManifest = serde_json::from_str(&buffer)
-                .map_err(|_| Epi4youError::FailedToParseFileContent)?;
+                .map_err(|err| Epi4youError::FailedToParseFileContent(err.into()))?;
 
             if manifest.is_trusted() {
                 return Ok(manifest);
@@ -232,24 +237,31 @@ impl Epi2MeManifest {
     ) -> Result<PathBuf, Epi4youError> {
         log::info!("untar of file [{:?}] into [{:?}]", tarfile, temp_dir);
 
-        let file =
-            File::open(tarfile).map_err(|_| Epi4youError::FailedToReadPath(tarfile.clone()))?;
-        let mut archive = Archive::new(file);
+        let file = File::open(tarfile)
+            .map_err(|err| Epi4youError::FailedToReadPath(tarfile.clone(), err.into()))?;
+        let size = file
+            .metadata()
+            .map_err(|err| Epi4youError::FailedToReadPath(tarfile.clone(), err.into()))?
+            .len();
+        let progress = Progress::new("extracting", size);
+        let mut archive = Archive::new(progress.wrap_read(file));
 
         for entry in archive
             .entries()
-            .map_err(|_| Epi4youError::ErrorInUnpackingTarElement)?
+            .map_err(|err| Epi4youError::ErrorInUnpackingTarElement(err.into()))?
         {
-            let mut file = entry.map_err(|_| Epi4youError::ErrorInUnpackingTarElement)?;
+            let mut file =
+                entry.map_err(|err| Epi4youError::ErrorInUnpackingTarElement(err.into()))?;
             let fp = file
                 .path()
-                .map_err(|_| Epi4youError::ErrorInUnpackingTarElement)?
+                .map_err(|err| Epi4youError::ErrorInUnpackingTarElement(err.into()))?
                 .into_owned();
             log::debug!("unpacking [{:?}] to [{:?}]", fp, temp_dir);
 
             file.unpack_in(temp_dir)
-                .map_err(|_| Epi4youError::ErrorInUnpackingTarElement)?;
+                .map_err(|err| Epi4youError::ErrorInUnpackingTarElement(err.into()))?;
         }
+        progress.finish();
 
         Ok(temp_dir.to_owned())
     }
@@ -272,8 +284,15 @@ impl Epi2MeManifest {
     ///
     /// Today the most concrete path is desktop analysis import, but the method
     /// is intentionally shaped to become the single content router for all
-    /// manifest payload variants.
-    pub fn process_container_content(&self, temp_dir: &PathBuf) -> Result<(), Epi4youError> {
+    /// manifest payload variants. Analyses are registered in the installation
+    /// described by `setup`, and their database records are returned so
+    /// callers can report where they landed.
+    pub fn process_container_content(
+        &self,
+        setup: &Epi2meSetup,
+        temp_dir: &PathBuf,
+    ) -> Result<Vec<Epi2MeAnalysis>, Epi4youError> {
+        let mut imported = Vec::new();
         for x in &self.payload {
             match x {
                 Epi2MeContent::Epi2meWf(epi2me_workflow) => {
@@ -281,7 +300,11 @@ impl Epi2MeManifest {
                 }
                 Epi2MeContent::Epi2mePayload(desktop_analysis) => {
                     log::info!("importing DesktopAnalysis [{}]", &desktop_analysis.id);
-                    app_db::insert_untarred_desktop_analysis(desktop_analysis, temp_dir);
+                    imported.push(app_db::insert_untarred_desktop_analysis(
+                        setup,
+                        desktop_analysis,
+                        temp_dir,
+                    )?);
                 }
                 Epi2MeContent::Epi2meContainer(epi2me_container) => {
                     log::info!("importing Epi2meContainer [{}]", &epi2me_container.workflow);
@@ -289,7 +312,7 @@ impl Epi2MeManifest {
             }
         }
 
-        Ok(())
+        Ok(imported)
     }
 
     /// Verifies that the stored signature matches the manifest's current value.
@@ -335,30 +358,46 @@ impl Epi2MeManifest {
     }
 
     /// Writes the signed manifest to disk.
-    pub fn write(&mut self, dest: &PathBuf) {
-        println!("writing manifest to path [{:?}]", dest);
-        if fs::write(dest, self.to_string()).is_err() {
-            println!("Error with writing manifest to file");
-        }
+    pub fn write(&mut self, dest: &PathBuf) -> Result<(), Epi4youError> {
+        log::info!("writing manifest to path [{:?}]", dest);
+        fs::write(dest, self.to_string())
+            .map_err(|err| Epi4youError::FailedToWritePath(dest.clone(), err.into()))
     }
 }
 
 /// Computes a SHA-256 digest for a file on disk.
-pub fn sha256_digest(path: &str) -> String {
-    let input = File::open(path).unwrap();
+pub fn sha256_digest(path: &Path) -> Result<String, Epi4youError> {
+    let input = File::open(path)
+        .map_err(|err| Epi4youError::FailedToReadPath(path.to_path_buf(), err.into()))?;
+    sha256_reader_digest(path, input)
+}
+
+/// Computes a SHA-256 digest for a file, counting the bytes read as progress.
+pub fn sha256_digest_with_progress(
+    path: &Path,
+    progress: &Progress,
+) -> Result<String, Epi4youError> {
+    let input = File::open(path)
+        .map_err(|err| Epi4youError::FailedToReadPath(path.to_path_buf(), err.into()))?;
+    sha256_reader_digest(path, progress.wrap_read(input))
+}
+
+fn sha256_reader_digest<R: Read>(path: &Path, input: R) -> Result<String, Epi4youError> {
     let mut reader = BufReader::new(input);
 
     let mut context = Context::new(&SHA256);
     let mut buffer = [0; 1024];
     loop {
-        let count = reader.read(&mut buffer).unwrap();
+        let count = reader
+            .read(&mut buffer)
+            .map_err(|err| Epi4youError::FailedToReadPath(path.to_path_buf(), err.into()))?;
         if count == 0 {
             break;
         }
         context.update(&buffer[..count]);
     }
 
-    HEXUPPER.encode(context.finish().as_ref())
+    Ok(HEXUPPER.encode(context.finish().as_ref()))
 }
 
 /// Computes a SHA-256 digest for an in-memory string payload.